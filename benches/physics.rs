@@ -0,0 +1,69 @@
+//! How long the physics takes to play out a busy match, to check refactors for regressions.
+//!
+//! Run with `cargo bench`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use pong::{
+    physics::{AiConfig, GameState, InputState, Player},
+    vertex::{Vertex, QUAD_UVS},
+};
+
+/// Same length of physics step as the game itself.
+const FIXED_DT: f64 = 1. / 120.;
+const STEPS: usize = 10_000;
+
+/// A quad centred on `center_x` on the middle line, `half_size` across either way.
+fn quad(center_x: f32, half_size: [f32; 2]) -> Vec<Vertex> {
+    QUAD_UVS
+        .map(|[x, y]| Vertex {
+            position: [center_x + x * half_size[0], y * half_size[1], 0.],
+            color: [1., 1., 1.],
+            uv: [x, y],
+        })
+        .to_vec()
+}
+
+/// A paddle of the game's default size, centred on `center_x`.
+fn paddle(center_x: f32) -> Player {
+    Player {
+        vertices: quad(center_x, [0.015, 0.2]),
+        velocity: 0.,
+    }
+}
+
+/// Three balls and power-ups between two computer players, so there's always something to
+/// collide with.
+fn busy_game() -> GameState {
+    let mut game = GameState::new(
+        paddle(-0.785),
+        paddle(0.785),
+        quad(0., [0.02, 0.02]),
+        3,
+        Some(AiConfig::HARD),
+        1,
+    );
+    game.p1_ai = Some(AiConfig::HARD);
+    game.power_up_interval = Some(2.);
+    game.serve_delay = 0.;
+    game.serve();
+    game
+}
+
+fn update(c: &mut Criterion) {
+    let input = InputState::default();
+    c.bench_function("10k updates, 3 balls", |b| {
+        b.iter_batched(
+            busy_game,
+            |mut game| {
+                for _ in 0..STEPS {
+                    std::hint::black_box(game.update(FIXED_DT, &input));
+                }
+                game
+            },
+            BatchSize::LargeInput,
+        );
+    });
+}
+
+criterion_group!(benches, update);
+criterion_main!(benches);
@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use rodio::{
+    source::{Amplify, Buffered, SineWave, TakeDuration},
+    OutputStream, OutputStreamHandle, Source,
+};
+
+/// A short sine tone, rendered once and cloned for every playback.
+type Tone = Buffered<Amplify<TakeDuration<SineWave>>>;
+
+fn tone(frequency: f32, millis: u64) -> Tone {
+    SineWave::new(frequency)
+        .take_duration(Duration::from_millis(millis))
+        .amplify(0.2)
+        .buffered()
+}
+
+/// Sound effects, mixed on rodio's own thread so playing one never blocks the frame.
+pub struct Audio {
+    // Dropping the stream stops all sound, so it has to live as long as the handle
+    _stream: OutputStream,
+    handle: OutputStreamHandle,
+    hit: Tone,
+    wall: Tone,
+    score: Tone,
+}
+
+impl Audio {
+    /// Returns `None` when no output device is available.
+    pub fn new() -> Option<Self> {
+        let (stream, handle) = OutputStream::try_default().ok()?;
+        Some(Audio {
+            _stream: stream,
+            handle,
+            hit: tone(660., 60),
+            wall: tone(440., 40),
+            score: tone(220., 400),
+        })
+    }
+
+    pub fn play_hit(&self) {
+        self.play(&self.hit);
+    }
+
+    pub fn play_wall(&self) {
+        self.play(&self.wall);
+    }
+
+    pub fn play_score(&self) {
+        self.play(&self.score);
+    }
+
+    fn play(&self, tone: &Tone) {
+        // A missed beep isn't worth interrupting the game over
+        let _ = self.handle.play_raw(tone.clone());
+    }
+}
@@ -0,0 +1,19 @@
+use thiserror::Error;
+
+/// Failures setting up the window or the GPU, reported to the player instead of panicking.
+#[derive(Debug, Error)]
+pub enum GameError {
+    #[error("Failed to initialize GLFW: {0}")]
+    Glfw(#[from] glfw::InitError),
+    #[error("Failed to create the game window")]
+    Window,
+    #[error("Failed to get a handle to the game window: {0}")]
+    WindowHandle(#[from] wgpu::rwh::HandleError),
+    /// Lists every backend that was tried.
+    #[error("No compatible graphics adapter found, tried {0}")]
+    NoAdapter(String),
+    #[error("Failed to open the graphics device: {0}")]
+    Device(#[from] wgpu::RequestDeviceError),
+    #[error("Failed to set up the network connection: {0}")]
+    Network(std::io::Error),
+}
@@ -0,0 +1,39 @@
+use gilrs::{Axis, Button, Gilrs};
+use pong::physics::InputState;
+
+/// How far the left stick has to be pushed before it counts as a held direction.
+const STICK_DEADZONE: f32 = 0.3;
+
+/// Controller input: the first connected gamepad drives player 1, the second player 2.
+pub struct Gamepads {
+    gilrs: Gilrs,
+}
+
+impl Gamepads {
+    /// Returns `None` when the platform's gamepad backend is unavailable.
+    pub fn new() -> Option<Self> {
+        Gilrs::new().ok().map(|gilrs| Gamepads { gilrs })
+    }
+
+    /// Drains pending controller events and adds the held directions on top of `input`.
+    pub fn apply(&mut self, input: &mut InputState) {
+        while self.gilrs.next_event().is_some() {}
+
+        let mut gamepads = self.gilrs.gamepads().map(|(_, gamepad)| {
+            let stick = gamepad.value(Axis::LeftStickY);
+            (
+                stick > STICK_DEADZONE || gamepad.is_pressed(Button::DPadUp),
+                stick < -STICK_DEADZONE || gamepad.is_pressed(Button::DPadDown),
+            )
+        });
+
+        if let Some((up, down)) = gamepads.next() {
+            input.p1_up |= up;
+            input.p1_down |= down;
+        }
+        if let Some((up, down)) = gamepads.next() {
+            input.p2_up |= up;
+            input.p2_down |= down;
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use glfw::Key;
+
+/// Keys moving the paddles, read from the `[keys]` table of the settings file.
+#[derive(Debug, Clone, Copy)]
+pub struct KeyBindings {
+    pub p1_up: Key,
+    pub p1_down: Key,
+    pub p2_up: Key,
+    pub p2_down: Key,
+}
+
+/// Keys the game itself handles, which a paddle can't take without losing what they do.
+const RESERVED_KEYS: [(Key, &str); 13] = [
+    (Key::Space, "starting and charging the serve"),
+    (Key::P, "pause"),
+    (Key::O, "the options menu"),
+    (Key::R, "restart"),
+    (Key::Escape, "quitting and closing menus"),
+    (Key::F3, "collision boxes"),
+    (Key::F5, "saving"),
+    (Key::F9, "loading"),
+    (Key::F11, "fullscreen"),
+    (Key::F12, "screenshots"),
+    (Key::LeftBracket, "slowing time down"),
+    (Key::RightBracket, "speeding time up"),
+    (Key::GraveAccent, "the debug overlay"),
+];
+
+impl KeyBindings {
+    /// Rejects bindings that use a key twice or take one the game already handles.
+    pub fn validate(&self) -> Result<(), String> {
+        let bindings = [
+            ("p1_up", self.p1_up),
+            ("p1_down", self.p1_down),
+            ("p2_up", self.p2_up),
+            ("p2_down", self.p2_down),
+        ];
+        for (index, &(name, key)) in bindings.iter().enumerate() {
+            if let Some((other, _)) = bindings[index + 1..]
+                .iter()
+                .find(|&&(_, other_key)| other_key == key)
+            {
+                return Err(format!("`{name}` and `{other}` are both bound to {key:?}"));
+            }
+            if let Some((_, used_for)) =
+                RESERVED_KEYS.iter().find(|&&(reserved, _)| reserved == key)
+            {
+                return Err(format!(
+                    "`{name}` can't be bound to {key:?}, which is used for {used_for}"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            p1_up: Key::W,
+            p1_down: Key::S,
+            p2_up: Key::Up,
+            p2_down: Key::Down,
+        }
+    }
+}
+
+/// Looks up a key by the name of its `glfw::Key` variant, such as `W`, `Up` or `LeftShift`.
+/// Digits go by `0` to `9` as well as `Num0` to `Num9`.
+pub fn parse_key(name: &str) -> Result<Key, String> {
+    let key = match name {
+        "A" => Key::A,
+        "B" => Key::B,
+        "C" => Key::C,
+        "D" => Key::D,
+        "E" => Key::E,
+        "F" => Key::F,
+        "G" => Key::G,
+        "H" => Key::H,
+        "I" => Key::I,
+        "J" => Key::J,
+        "K" => Key::K,
+        "L" => Key::L,
+        "M" => Key::M,
+        "N" => Key::N,
+        "O" => Key::O,
+        "P" => Key::P,
+        "Q" => Key::Q,
+        "R" => Key::R,
+        "S" => Key::S,
+        "T" => Key::T,
+        "U" => Key::U,
+        "V" => Key::V,
+        "W" => Key::W,
+        "X" => Key::X,
+        "Y" => Key::Y,
+        "Z" => Key::Z,
+        "0" | "Num0" => Key::Num0,
+        "1" | "Num1" => Key::Num1,
+        "2" | "Num2" => Key::Num2,
+        "3" | "Num3" => Key::Num3,
+        "4" | "Num4" => Key::Num4,
+        "5" | "Num5" => Key::Num5,
+        "6" | "Num6" => Key::Num6,
+        "7" | "Num7" => Key::Num7,
+        "8" | "Num8" => Key::Num8,
+        "9" | "Num9" => Key::Num9,
+        "Up" => Key::Up,
+        "Down" => Key::Down,
+        "Left" => Key::Left,
+        "Right" => Key::Right,
+        "Space" => Key::Space,
+        "Enter" => Key::Enter,
+        "Tab" => Key::Tab,
+        "Backspace" => Key::Backspace,
+        "LeftShift" => Key::LeftShift,
+        "RightShift" => Key::RightShift,
+        "LeftControl" => Key::LeftControl,
+        "RightControl" => Key::RightControl,
+        "LeftAlt" => Key::LeftAlt,
+        "RightAlt" => Key::RightAlt,
+        "Comma" => Key::Comma,
+        "Period" => Key::Period,
+        "Slash" => Key::Slash,
+        "Semicolon" => Key::Semicolon,
+        "Apostrophe" => Key::Apostrophe,
+        "Minus" => Key::Minus,
+        "Equal" => Key::Equal,
+        "Kp0" => Key::Kp0,
+        "Kp1" => Key::Kp1,
+        "Kp2" => Key::Kp2,
+        "Kp3" => Key::Kp3,
+        "Kp4" => Key::Kp4,
+        "Kp5" => Key::Kp5,
+        "Kp6" => Key::Kp6,
+        "Kp7" => Key::Kp7,
+        "Kp8" => Key::Kp8,
+        "Kp9" => Key::Kp9,
+        _ => return Err(format!("Unknown key `{name}`")),
+    };
+
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bindings_reject_duplicate_and_reserved_keys() {
+        assert!(KeyBindings::default().validate().is_ok());
+        let duplicate = KeyBindings {
+            p1_down: Key::W,
+            ..KeyBindings::default()
+        };
+        assert!(duplicate.validate().is_err());
+        let reserved = KeyBindings {
+            p2_up: Key::P,
+            ..KeyBindings::default()
+        };
+        assert!(reserved.validate().is_err());
+    }
+}
@@ -0,0 +1,9 @@
+//! The game's simulation, kept apart from the window and GPU so the benchmarks can drive it too.
+#![warn(clippy::all, clippy::pedantic)]
+// Only the game and its benchmarks use the library, `#[must_use]` everywhere would be noise
+#![allow(clippy::must_use_candidate, clippy::return_self_not_must_use)]
+
+pub mod math;
+pub mod pause_menu;
+pub mod physics;
+pub mod vertex;
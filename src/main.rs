@@ -1,65 +1,610 @@
 #![warn(clippy::all, clippy::pedantic)]
 use std::{
-    ops::Add,
+    collections::VecDeque,
+    path::Path,
     sync::{Arc, Mutex},
+    time::Instant,
 };
 
-use glfw::{fail_on_errors, Action, Context, Window};
-use radians::Wrap64;
+use glfw::{log_errors, Action, Context, Window};
+use log::{debug, error, info, warn};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 use wgpu::{
     self, util::DeviceExt, BackendOptions, Backends, BufferUsages, Color, InstanceDescriptor,
     InstanceFlags, PipelineCompilationOptions, RenderPipelineDescriptor, RequestAdapterOptionsBase,
     VertexState,
 };
-mod physics;
+use wgpu_text::{
+    glyph_brush::{ab_glyph::FontRef, HorizontalAlign, Layout, Section, Text},
+    BrushBuilder, TextBrush,
+};
+#[cfg(feature = "audio")]
+mod audio;
+mod error;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+mod keys;
+mod net;
+mod options;
+mod profile;
+mod replay;
+mod screenshot;
+mod settings;
+mod theme;
+
+use error::GameError;
+use options::Options;
+use pong::{
+    math::Vec2,
+    pause_menu::{LiveSettings, MenuKey, MenuOutcome, PauseMenu},
+    physics::{
+        self, AiConfig, Ball, BallShape, GameEvent, GameState, InputDelay, InputState, Match,
+        Overtime, Player, PowerUp, Side, MAX_POWER_UPS,
+    },
+    vertex::{Vertex, QUAD_UVS},
+};
+use profile::FrameProfile;
+use replay::Replay;
+use screenshot::Screenshot;
+
+/// Length of one physics step, in seconds.
+const FIXED_DT: f64 = 1. / 120.;
+
+/// Background tint shown while the game is paused.
+const PAUSED_COLOR: Color = Color {
+    r: 0.15,
+    g: 0.15,
+    b: 0.2,
+    a: 1.,
+};
+
+// Slow motion and fast forward bounds for `[` and `]`
+const MIN_TIME_SCALE: f64 = 0.1;
+const MAX_TIME_SCALE: f64 = 4.;
+
+/// Longest wait for window events, in seconds, while the GPU is being set up.
+const SETUP_EVENT_TIMEOUT: f64 = 1. / 30.;
+
+/// Where F5 saves the match and F9 loads it from.
+const SAVE_PATH: &str = "pong_save.json";
+
+/// Horizontal distance from each goal line to the middle of the paddle guarding it.
+const PADDLE_INSET: f32 = 0.215;
+const PADDLE_HALF_WIDTH: f32 = 0.015;
+/// Default paddle half-height, overridable with `--paddle-half-height`.
+const PADDLE_HALF_HEIGHT: f32 = 0.2;
+
+/// Default ball half-size, overridable with `--ball-size`.
+const BALL_HALF_SIZE: f32 = 0.02;
+
+/// The ball's vertices run top-right, top-left, bottom-left, bottom-right.
+const BALL_INDICES: [u16; 6] = [0, 1, 2, 0, 2, 3];
+
+/// Frames averaged over for every input-to-photon estimate logged with `--vsync off`.
+const LATENCY_LOG_FRAMES: u32 = 1_000;
+
+/// How many past ball positions leave a fading square behind it.
+const TRAIL_LENGTH: usize = 12;
+
+/// Physics steps kept for the replay after a point, about three seconds at `FIXED_DT`.
+const REPLAY_STEPS: usize = 360;
+/// The replay runs at half speed.
+const REPLAY_SPEED: f64 = 0.5;
+
+// Screen shake after a paddle hit at `HARD_HIT_SPEED` or faster: how far the view jumps at first,
+// and the time in seconds for it to die down to about a third of that
+const HARD_HIT_SPEED: f64 = 1.2;
+const SHAKE_STRENGTH: f64 = 0.025;
+const SHAKE_DECAY_TIME: f64 = 0.08;
+
+/// Height of the wall bars drawn above and below the field with `--aspect`.
+const WALL_THICKNESS: f32 = 0.04;
+
+/// Number of dashes making up the centre net.
+const NET_DASH_COUNT: u16 = 15;
+/// Vertical space between two dashes of the net.
+const NET_DASH_GAP: f32 = 0.05;
+
+/// Where the match is at, sent along with every snapshot so the client shows the same.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum GamePhase {
+    /// The computer playing itself until a key is pressed.
+    Demo,
+    Menu,
+    Playing,
+    /// The last few seconds before a point, in slow motion.
+    Replay,
+    GameOver,
+}
+
+/// How fast the game runs after `steps` doublings of its speed, or halvings when negative, kept
+/// within `MIN_TIME_SCALE..=MAX_TIME_SCALE`.
+fn time_scale(steps: i32) -> f64 {
+    2_f64.powi(steps).clamp(MIN_TIME_SCALE, MAX_TIME_SCALE)
+}
+
+/// The quads of the power-ups waiting on the field, padded with zero-area quads up to
+/// `MAX_POWER_UPS` so the buffer layout never changes.
+fn power_up_geometry(power_ups: &[PowerUp]) -> Vec<Vertex> {
+    let unused = Vertex {
+        position: [0., 0., 0.],
+        color: [0., 0., 0.],
+        uv: [0., 0.],
+    };
+    let mut vertices: Vec<Vertex> = power_ups
+        .iter()
+        .flat_map(|power_up| power_up.vertices.iter().copied())
+        .collect();
+    vertices.resize(MAX_POWER_UPS * 4, unused);
+    vertices
+}
+
+/// Builds `TRAIL_LENGTH` quads over the recorded ball centres, newest first, shrinking and
+/// darkening with age. Unused slots collapse to zero-area quads so the buffer layout never changes.
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+fn trail_geometry(trail: &VecDeque<Vec2>, color: [f32; 3], ball_size: f32) -> Vec<Vertex> {
+    // Between the ball and the net
+    const DEPTH: f32 = 0.25;
+    // The newest square is a little smaller than the ball
+    let half_size = ball_size * 0.75;
+
+    (0..TRAIL_LENGTH)
+        .flat_map(|age| {
+            let fade = 1. - (age + 1) as f32 / (TRAIL_LENGTH + 1) as f32;
+            let ([x, y], half) = match trail.get(age) {
+                Some(center) => ([center.x as f32, center.y as f32], half_size * fade),
+                None => ([0., 0.], 0.),
+            };
+            let color = color.map(|component| component * fade * 0.5);
+            [
+                [x - half, y + half],
+                [x - half, y - half],
+                [x + half, y + half],
+                [x + half, y - half],
+            ]
+            .into_iter()
+            .zip(QUAD_UVS)
+            .map(move |([x, y], uv)| Vertex {
+                position: [x, y, DEPTH],
+                color,
+                uv,
+            })
+        })
+        .collect()
+}
+
+/// Builds the bars just outside the top and bottom walls, spanning the field's `half_width`.
+fn wall_geometry(half_width: f32) -> Vec<Vertex> {
+    // Level with the net
+    const DEPTH: f32 = 0.5;
+    const COLOR: [f32; 3] = [0.5, 0.5, 0.5];
+
+    [1., -1. - WALL_THICKNESS]
+        .into_iter()
+        .flat_map(|bottom| {
+            let top = bottom + WALL_THICKNESS;
+            [
+                [-half_width, top, DEPTH],
+                [-half_width, bottom, DEPTH],
+                [half_width, top, DEPTH],
+                [half_width, bottom, DEPTH],
+            ]
+            .into_iter()
+            .zip(QUAD_UVS)
+            .map(|(position, uv)| Vertex {
+                position,
+                color: COLOR,
+                uv,
+            })
+        })
+        .collect()
+}
+
+/// Builds the bar under the field that fills up from the left as the serve is charged, `fraction`
+/// going from 0 to 1.
+fn charge_bar_geometry(fraction: f32) -> Vec<Vertex> {
+    const LEFT: f32 = -0.15;
+    const WIDTH: f32 = 0.3;
+    const TOP: f32 = -0.85;
+    const HEIGHT: f32 = 0.03;
+    // In front of the net
+    const DEPTH: f32 = 0.4;
+    const COLOR: [f32; 3] = [1., 0.8, 0.2];
 
-struct Player {
-    vertices: Vec<Vertex>,
+    let right = LEFT + WIDTH * fraction;
+    [
+        [LEFT, TOP, DEPTH],
+        [LEFT, TOP - HEIGHT, DEPTH],
+        [right, TOP, DEPTH],
+        [right, TOP - HEIGHT, DEPTH],
+    ]
+    .into_iter()
+    .zip(QUAD_UVS)
+    .map(|(position, uv)| Vertex {
+        position,
+        color: COLOR,
+        uv,
+    })
+    .collect()
 }
 
-struct Ball {
-    vertices: Vec<Vertex>,
+/// Builds the bar standing in for player 2 with `--wall`, just past the right edge at
+/// `half_width`.
+fn practice_wall_geometry(half_width: f32) -> Vec<Vertex> {
+    // Level with the net
+    const DEPTH: f32 = 0.5;
+    const COLOR: [f32; 3] = [0.5, 0.5, 0.5];
+
+    [
+        [half_width, 1., DEPTH],
+        [half_width, -1., DEPTH],
+        [half_width + WALL_THICKNESS, 1., DEPTH],
+        [half_width + WALL_THICKNESS, -1., DEPTH],
+    ]
+    .into_iter()
+    .zip(QUAD_UVS)
+    .map(|(position, uv)| Vertex {
+        position,
+        color: COLOR,
+        uv,
+    })
+    .collect()
+}
+
+/// Builds outlines of the `(min, max)` boxes for the F3 overlay, padded with empty boxes up to
+/// `capacity` so the buffer layout never changes. With `wireframe` each box is its four corners
+/// for the line pipeline to join, otherwise four thin quads along its sides.
+#[allow(clippy::cast_possible_truncation)]
+fn outline_geometry(boxes: &[(Vec2, Vec2)], capacity: usize, wireframe: bool) -> Vec<Vertex> {
+    const THICKNESS: f32 = 0.004;
+    // In front of everything
+    const DEPTH: f32 = 0.;
+    const COLOR: [f32; 3] = [0.2, 1., 0.2];
+
+    let quad = |[left, bottom]: [f32; 2], [right, top]: [f32; 2]| {
+        [
+            [left, top, DEPTH],
+            [left, bottom, DEPTH],
+            [right, top, DEPTH],
+            [right, bottom, DEPTH],
+        ]
+        .into_iter()
+        .zip(QUAD_UVS)
+        .map(|(position, uv)| Vertex {
+            position,
+            color: COLOR,
+            uv,
+        })
+    };
+    let unused = Vertex {
+        position: [0., 0., DEPTH],
+        color: COLOR,
+        uv: [0., 0.],
+    };
+    let mut vertices: Vec<Vertex> = boxes
+        .iter()
+        .take(capacity)
+        .flat_map(|(min, max)| {
+            let [left, bottom, right, top] = [min.x, min.y, max.x, max.y].map(|value| value as f32);
+            let sides = if wireframe {
+                vec![([left, bottom], [right, top])]
+            } else {
+                vec![
+                    ([left, bottom], [left + THICKNESS, top]),
+                    ([right - THICKNESS, bottom], [right, top]),
+                    ([left, bottom], [right, bottom + THICKNESS]),
+                    ([left, top - THICKNESS], [right, top]),
+                ]
+            };
+            sides.into_iter().flat_map(move |(from, to)| quad(from, to))
+        })
+        .collect();
+    let per_box = if wireframe { 4 } else { 16 };
+    vertices.resize(capacity * per_box, unused);
+    vertices
+}
+
+/// Indices for [`outline_geometry`] with room for `capacity` boxes: the four edges of each box
+/// for the line pipeline, or two triangles for each of the thin quads.
+fn outline_indices(capacity: usize, wireframe: bool) -> Vec<u16> {
+    let boxes = u16::try_from(capacity).expect("Too many collision boxes");
+    if wireframe {
+        (0..boxes)
+            .flat_map(|index| {
+                let base = index * 4;
+                // Left, bottom, right and top, never the diagonal between opposite corners
+                [0, 1, 1, 3, 3, 2, 2, 0].map(|corner| base + corner)
+            })
+            .collect()
+    } else {
+        quad_indices(boxes * 4)
+    }
+}
+
+/// Builds a single dash of the centre line around the origin, drawn once per entry of
+/// [`net_dash_offsets`].
+fn net_dash_geometry() -> Vec<Vertex> {
+    const HALF_WIDTH: f32 = 0.005;
+    // Behind the paddles and ball
+    const DEPTH: f32 = 0.5;
+    const COLOR: [f32; 3] = [0.5, 0.5, 0.5];
+
+    let half_height = (2. / f32::from(NET_DASH_COUNT) - NET_DASH_GAP) / 2.;
+    [
+        [-HALF_WIDTH, half_height, DEPTH],
+        [-HALF_WIDTH, -half_height, DEPTH],
+        [HALF_WIDTH, half_height, DEPTH],
+        [HALF_WIDTH, -half_height, DEPTH],
+    ]
+    .into_iter()
+    .zip(QUAD_UVS)
+    .map(|(position, uv)| Vertex {
+        position,
+        color: COLOR,
+        uv,
+    })
+    .collect()
 }
 
-struct BallPhysics {
-    position: [f32; 2],
-    velocity: [f32; 2],
-    acceleration: [f32; 2],
+/// Where each dash of the net sits, top to bottom, with `NET_DASH_GAP` between them.
+fn net_dash_offsets() -> Vec<Instance> {
+    let slot = 2. / f32::from(NET_DASH_COUNT);
+    (0..NET_DASH_COUNT)
+        .map(|dash| Instance {
+            offset: [0., 1. - slot * (f32::from(dash) + 0.5)],
+        })
+        .collect()
 }
 
-struct PaddlePhysics {
-    position: [f32; 2],
-    velocity: [f32; 2],
-    acceleration: [f32; 2],
+/// Indices for `count` quads laid out as top-left, bottom-left, top-right, bottom-right.
+fn quad_indices(count: u16) -> Vec<u16> {
+    (0..count)
+        .flat_map(|quad| {
+            let base = quad * 4;
+            [base, base + 1, base + 2, base + 2, base + 1, base + 3]
+        })
+        .collect()
 }
 
+/// A column-major 4x4 matrix, as WGSL lays out `mat4x4<f32>`.
+type Matrix = [[f32; 4]; 4];
+
+const IDENTITY: Matrix = [
+    [1., 0., 0., 0.],
+    [0., 1., 0., 0.],
+    [0., 0., 1., 0.],
+    [0., 0., 0., 1.],
+];
+
+/// Corner radius of the paddles and square balls, as a fraction of their shorter half-extent.
+/// Everything else keeps square corners.
+const CORNER_ROUNDNESS: f32 = 0.5;
+
+/// Everything the shaders know about the object being drawn: where its model matrix puts it,
+/// and how round its corners are.
 #[repr(C)]
 #[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
-struct Vertex {
-    position: [f32; 3],
-    color: [f32; 3],
+struct ModelUniform {
+    transform: Matrix,
+    roundness: f32,
+    /// Uniform structs are sized in multiples of 16 bytes.
+    _padding: [f32; 3],
+}
+
+/// An axis-aligned quad's vertices moved to corners at `-1` and `1`, which its uv coordinates
+/// already give, for [`quad_transform`] to put back in place.
+fn unit_quad(vertices: &[Vertex]) -> Vec<Vertex> {
+    vertices
+        .iter()
+        .map(|vertex| Vertex {
+            position: [vertex.uv[0], vertex.uv[1], vertex.position[2]],
+            ..*vertex
+        })
+        .collect()
+}
+
+/// Lower-left and upper-right corners of the box around the vertices.
+fn quad_bounds(vertices: &[Vertex]) -> ([f32; 2], [f32; 2]) {
+    vertices.iter().fold(
+        ([f32::INFINITY; 2], [f32::NEG_INFINITY; 2]),
+        |(min, max), vertex| {
+            let [x, y, _] = vertex.position;
+            (
+                [min[0].min(x), min[1].min(y)],
+                [max[0].max(x), max[1].max(y)],
+            )
+        },
+    )
+}
+
+/// The model matrix stretching a [`unit_quad`] over a blend of two snapshots of the same quad,
+/// `t = 0` giving `from` and `t = 1` giving `to`.
+#[allow(clippy::cast_possible_truncation)]
+fn quad_transform(from: &[Vertex], to: &[Vertex], t: f64) -> Matrix {
+    let t = t as f32;
+    let ((from_min, from_max), (to_min, to_max)) = (quad_bounds(from), quad_bounds(to));
+    let lerp =
+        |from: [f32; 2], to: [f32; 2]| [0, 1].map(|axis| from[axis] + (to[axis] - from[axis]) * t);
+    let (min, max) = (lerp(from_min, to_min), lerp(from_max, to_max));
+    let [center_x, center_y] = [0, 1].map(|axis| f32::midpoint(min[axis], max[axis]));
+    let [half_x, half_y] = [0, 1].map(|axis| (max[axis] - min[axis]) / 2.);
+    [
+        [half_x, 0., 0., 0.],
+        [0., half_y, 0., 0.],
+        [0., 0., 1., 0.],
+        [center_x, center_y, 0., 1.],
+    ]
+}
+
+/// One object on screen, with indices relative to its own vertices. Static ones, like the net
+/// and walls, are uploaded once; dynamic ones get their vertices rewritten with
+/// [`Drawable::update`] whenever they change. Paddles and balls keep static unit quads and are
+/// moved by their model matrix instead, set with [`Drawable::set_transform`].
+struct Drawable {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_count: u32,
+    transform_buffer: wgpu::Buffer,
+    transform_bind_group: wgpu::BindGroup,
+    /// Per-copy offsets and how many copies there are, for a drawable drawn instanced.
+    instances: Option<(wgpu::Buffer, u32)>,
+}
+
+impl Drawable {
+    fn new(state: &State, label: &str, vertices: &[Vertex], indices: &[u16]) -> Self {
+        Self::with_usage(
+            state,
+            label,
+            vertices,
+            indices,
+            BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        )
+    }
+
+    /// A drawable whose vertices can never change after this.
+    fn new_static(state: &State, label: &str, vertices: &[Vertex], indices: &[u16]) -> Self {
+        Self::with_usage(state, label, vertices, indices, BufferUsages::VERTEX)
+    }
+
+    fn with_usage(
+        state: &State,
+        label: &str,
+        vertices: &[Vertex],
+        indices: &[u16],
+        usage: BufferUsages,
+    ) -> Self {
+        let device = &state.device;
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{label} Vertex Buffer")),
+            contents: bytemuck::cast_slice(vertices),
+            usage,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{label} Index Buffer")),
+            contents: bytemuck::cast_slice(indices),
+            usage: BufferUsages::INDEX,
+        });
+        let transform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{label} Transform Buffer")),
+            contents: bytemuck::cast_slice(&[ModelUniform {
+                transform: IDENTITY,
+                roundness: 0.,
+                _padding: [0.; 3],
+            }]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let transform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("{label} Transform Bind Group")),
+            layout: &state.model_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: transform_buffer.as_entire_binding(),
+            }],
+        });
+
+        Drawable {
+            vertex_buffer,
+            index_buffer,
+            index_count: u32::try_from(indices.len()).expect("Too many indices"),
+            transform_buffer,
+            transform_bind_group,
+            instances: None,
+        }
+    }
+
+    /// Draws a copy of the drawable for every one of `instances`, all in one draw call.
+    fn with_instances(self, state: &State, instances: &[Instance]) -> Self {
+        let buffer = state
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Instance Buffer"),
+                contents: bytemuck::cast_slice(instances),
+                usage: BufferUsages::VERTEX,
+            });
+        let count = u32::try_from(instances.len()).expect("Too many instances");
+        Drawable {
+            instances: Some((buffer, count)),
+            ..self
+        }
+    }
+
+    /// Returns the bytes queued for upload, as [`Self::update`] does.
+    fn set_transform(&self, queue: &wgpu::Queue, matrix: &Matrix) -> usize {
+        let bytes = bytemuck::cast_slice(matrix);
+        queue.write_buffer(&self.transform_buffer, 0, bytes);
+        bytes.len()
+    }
+
+    /// Rounds the corners by `roundness` of the shorter half-extent, 1 giving a circle.
+    fn set_roundness(&self, queue: &wgpu::Queue, roundness: f32) {
+        let offset = std::mem::size_of::<Matrix>() as wgpu::BufferAddress;
+        queue.write_buffer(
+            &self.transform_buffer,
+            offset,
+            bytemuck::bytes_of(&roundness),
+        );
+    }
+
+    /// Overwrites the vertices, which must keep the count the drawable was created with.
+    /// Returns the bytes queued for upload, for the frame rate counter to report.
+    fn update(&self, queue: &wgpu::Queue, vertices: &[Vertex]) -> usize {
+        let bytes = bytemuck::cast_slice(vertices);
+        queue.write_buffer(&self.vertex_buffer, 0, bytes);
+        bytes.len()
+    }
+}
+
+/// Per-instance data for drawables drawn many times over in one call, like the net's dashes.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Instance {
+    /// Added to every vertex position of this copy.
+    offset: [f32; 2],
 }
 
-impl Vertex {
+impl Instance {
     fn desc() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &[
-                wgpu::VertexAttribute {
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-                wgpu::VertexAttribute {
-                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
-                    shader_location: 1,
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-            ],
+            array_stride: std::mem::size_of::<Instance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[wgpu::VertexAttribute {
+                offset: 0,
+                shader_location: 3,
+                format: wgpu::VertexFormat::Float32x2,
+            }],
+        }
+    }
+}
+
+/// Window size in pixels, read by the vertex shader to correct for the aspect ratio, how far
+/// the whole view is shifted, for screen shake, and the half-extents of the area that has to fit
+/// in the window.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ScreenUniform {
+    resolution: [f32; 2],
+    offset: [f32; 2],
+    view_half_size: [f32; 2],
+}
+
+impl ScreenUniform {
+    #[allow(clippy::cast_precision_loss)]
+    fn new(
+        config: &wgpu::SurfaceConfiguration,
+        offset: [f32; 2],
+        view_half_size: [f32; 2],
+    ) -> Self {
+        ScreenUniform {
+            resolution: [config.width as f32, config.height as f32],
+            offset,
+            view_half_size,
         }
     }
 }
+
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
 struct State<'a> {
     surface: wgpu::Surface<'a>,
     device: wgpu::Device,
@@ -68,39 +613,317 @@ struct State<'a> {
     config: wgpu::SurfaceConfiguration,
     size: (i32, i32),
     render_pipeline: wgpu::RenderPipeline,
+    /// Draws drawables that come with an [`Instance`] buffer, once per instance.
+    instanced_pipeline: wgpu::RenderPipeline,
+    /// Draws the collision boxes as outlines, where the adapter can draw lines at all.
+    wireframe_pipeline: Option<wgpu::RenderPipeline>,
+    screen_buffer: wgpu::Buffer,
+    screen_bind_group: wgpu::BindGroup,
+    /// Layout of the model matrix every [`Drawable`] binds.
+    model_bind_group_layout: wgpu::BindGroupLayout,
+    /// Half-extents of the area kept fully in view, letterboxed to fit the window.
+    view_half_size: [f32; 2],
+    sample_count: u32,
+    msaa_view: Option<wgpu::TextureView>,
+    depth_view: wgpu::TextureView,
+    /// What the screen is cleared to every frame, unless `clear_color` overrides it.
+    background: Color,
+    /// A colour flooding the screen in place of the background, such as while paused.
+    clear_color: Option<Color>,
+    text_renderer: TextBrush<FontRef<'static>>,
 }
 
-impl<'a> State<'a> {
-    pub async fn new(window: &'a mut Window) -> Self {
-        let size = window.get_size();
+/// Picks the graphics backend from the `WGPU_BACKEND` environment variable.
+///
+/// Accepted values (case-insensitive) are `vulkan`, `metal`, `dx12`, `gl` and `all`.
+/// Anything else, or leaving the variable unset, lets wgpu try every backend.
+fn backends_from_env() -> Backends {
+    match std::env::var("WGPU_BACKEND")
+        .unwrap_or_default()
+        .to_lowercase()
+        .as_str()
+    {
+        "vulkan" => Backends::VULKAN,
+        "metal" => Backends::METAL,
+        "dx12" => Backends::DX12,
+        "gl" => Backends::GL,
+        _ => Backends::all(),
+    }
+}
+
+/// Prints every adapter on every backend, for picking a `WGPU_BACKEND` when the default choice
+/// is the wrong GPU.
+fn list_adapters() {
+    let instance = wgpu::Instance::new(&InstanceDescriptor {
+        backends: Backends::all(),
+        flags: InstanceFlags::default(),
+        backend_options: BackendOptions::default(),
+    });
+    let adapters = instance.enumerate_adapters(Backends::all());
+    if adapters.is_empty() {
+        println!("No adapters found");
+    }
+    for adapter in adapters {
+        let info = adapter.get_info();
+        println!(
+            "{} ({:?} backend, {:?})",
+            info.name, info.backend, info.device_type
+        );
+    }
+}
+
+/// Waits for `future` on another thread, handling window events meanwhile so the window can
+/// still be moved, resized and closed while the GPU takes its time.
+fn wait_handling_events<T: Send>(
+    glfw: &mut glfw::Glfw,
+    future: impl std::future::Future<Output = T> + Send,
+) -> T {
+    std::thread::scope(|scope| {
+        let worker = scope.spawn(|| pollster::block_on(future));
+        while !worker.is_finished() {
+            glfw.wait_events_timeout(SETUP_EVENT_TIMEOUT);
+        }
+        worker
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+/// Finds an adapter that can draw to `window`, trying the `WGPU_BACKEND` choice first, then
+/// Metal, DX12 and GL one at a time, and finally a software adapter on any backend.
+fn request_adapter(
+    glfw: &mut glfw::Glfw,
+    window: &Window,
+) -> Result<(wgpu::Surface<'static>, wgpu::Adapter), GameError> {
+    let requested = backends_from_env();
+    let attempts = [
+        (requested, false),
+        (Backends::METAL, false),
+        (Backends::DX12, false),
+        (Backends::GL, false),
+        (Backends::all(), true),
+    ];
+
+    let mut tried = Vec::new();
+    for (index, &(backends, force_fallback_adapter)) in attempts.iter().enumerate() {
+        // Don't ask a backend twice when it was the one requested
+        if attempts[..index].contains(&(backends, force_fallback_adapter)) {
+            continue;
+        }
+        let mut name = backends
+            .iter_names()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>()
+            .join("/");
+        if force_fallback_adapter {
+            name = format!("software on {name}");
+        }
+
         let instance = wgpu::Instance::new(&InstanceDescriptor {
-            backends: Backends::VULKAN,
+            backends,
             flags: InstanceFlags::default(),
             backend_options: BackendOptions::default(),
         });
+        let target = unsafe { wgpu::SurfaceTargetUnsafe::from_window(window) }?;
+        // A backend missing from this build or platform can't make a surface either
+        let surface = match unsafe { instance.create_surface_unsafe(target) } {
+            Ok(surface) => surface,
+            Err(error) => {
+                tried.push(format!("{name} ({error})"));
+                continue;
+            }
+        };
 
-        let target = unsafe { wgpu::SurfaceTargetUnsafe::from_window(&window) }
-            .expect("Failed to get target");
-        let surface =
-            unsafe { instance.create_surface_unsafe(target) }.expect("Failed to get surface");
-
-        let adapter = instance
-            .request_adapter(&RequestAdapterOptionsBase::default())
-            .await
-            .expect("Failed to get adapter");
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: Some("Device"),
-                    required_features: wgpu::Features::empty(),
-                    required_limits: wgpu::Limits::default(),
-                    memory_hints: Default::default(),
-                },
-                None,
-            )
-            .await
-            .expect("Failed to get device and queue");
+        let options = RequestAdapterOptionsBase {
+            power_preference: wgpu::PowerPreference::default(),
+            force_fallback_adapter,
+            compatible_surface: Some(&surface),
+        };
+        let adapter = wait_handling_events(glfw, instance.request_adapter(&options));
+        match adapter {
+            Some(adapter) => {
+                if !tried.is_empty() {
+                    warn!("No adapter on {}, using {name}", tried.join(", "));
+                }
+                return Ok((surface, adapter));
+            }
+            None => tried.push(name),
+        }
+    }
+
+    Err(GameError::NoAdapter(tried.join(", ")))
+}
+
+/// The highest sample count up to `requested` that both `format` and the depth buffer support.
+fn supported_sample_count(
+    adapter: &wgpu::Adapter,
+    format: wgpu::TextureFormat,
+    requested: u32,
+) -> u32 {
+    let color = adapter.get_texture_format_features(format).flags;
+    let depth = adapter.get_texture_format_features(DEPTH_FORMAT).flags;
+    [8, 4, 2]
+        .into_iter()
+        .filter(|&count| count <= requested)
+        .find(|&count| color.sample_count_supported(count) && depth.sample_count_supported(count))
+        .unwrap_or(1)
+}
+
+/// Creates the multisampled colour target matching the surface, or `None` without MSAA.
+fn create_msaa_view(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    sample_count: u32,
+) -> Option<wgpu::TextureView> {
+    if sample_count <= 1 {
+        return None;
+    }
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("MSAA Texture"),
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+}
+
+/// Creates the depth target matching the surface and the colour target's sample count.
+fn create_depth_view(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    sample_count: u32,
+) -> wgpu::TextureView {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Depth Texture"),
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format: DEPTH_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+/// Creates the pipeline every drawable goes through, or with `PolygonMode::Line` one tracing
+/// triangle edges over everything else for the collision boxes. An `instanced` pipeline also
+/// reads an [`Instance`] buffer, shifting each copy by its offset.
+fn create_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    polygon_mode: wgpu::PolygonMode,
+    instanced: bool,
+) -> wgpu::RenderPipeline {
+    let outline = polygon_mode == wgpu::PolygonMode::Line;
+    let (label, entry_point, buffers): (_, _, &[_]) = match (outline, instanced) {
+        (true, _) => ("Wireframe Pipeline", "vs_main", &[Vertex::desc()]),
+        (false, true) => (
+            "Instanced Pipeline",
+            "vs_instanced",
+            &[Vertex::desc(), Instance::desc()],
+        ),
+        (false, false) => ("Default Pipeline", "vs_main", &[Vertex::desc()]),
+    };
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        vertex: VertexState {
+            module: shader,
+            entry_point: Some(entry_point),
+            compilation_options: PipelineCompilationOptions::default(),
+            buffers,
+        },
+        primitive: wgpu::PrimitiveState {
+            topology: if outline {
+                wgpu::PrimitiveTopology::LineList
+            } else {
+                wgpu::PrimitiveTopology::TriangleList
+            },
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: (!outline).then_some(wgpu::Face::Back),
+            unclipped_depth: false,
+            polygon_mode,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            // Outlines show over everything without hiding any of it
+            depth_write_enabled: !outline,
+            depth_compare: if outline {
+                wgpu::CompareFunction::Always
+            } else {
+                wgpu::CompareFunction::LessEqual
+            },
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            // Lines have no inside for the rounded-corner coverage to work out
+            entry_point: Some(if outline { "fs_flat" } else { "fs_main" }),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        multiview: None,
+        cache: None,
+    })
+}
+
+impl<'a> State<'a> {
+    #[allow(clippy::too_many_lines)]
+    pub fn new(
+        glfw: &mut glfw::Glfw,
+        window: &'a mut Window,
+        present_mode: Option<wgpu::PresentMode>,
+        msaa: u32,
+        view_half_size: [f32; 2],
+    ) -> Result<Self, GameError> {
+        let size = window.get_framebuffer_size();
+        let (surface, adapter) = request_adapter(glfw, window)?;
+        // Without it the collision boxes fall back to thin quads
+        let wireframe = adapter
+            .features()
+            .contains(wgpu::Features::POLYGON_MODE_LINE);
+
+        let descriptor = wgpu::DeviceDescriptor {
+            label: Some("Device"),
+            required_features: if wireframe {
+                wgpu::Features::POLYGON_MODE_LINE
+            } else {
+                wgpu::Features::empty()
+            },
+            required_limits: wgpu::Limits::default(),
+            memory_hints: wgpu::MemoryHints::default(),
+        };
+        let (device, queue) =
+            wait_handling_events(glfw, adapter.request_device(&descriptor, None))?;
 
         let surface_caps = surface.get_capabilities(&adapter);
 
@@ -111,13 +934,37 @@ impl<'a> State<'a> {
             .copied()
             .unwrap_or(surface_caps.formats[0]);
 
+        // Printed even without logging, it's the first thing to ask for in a GPU bug report
+        let adapter_info = adapter.get_info();
+        println!(
+            "Adapter: {} ({:?}, {:?} backend), surface format {surface_format:?}",
+            adapter_info.name, adapter_info.device_type, adapter_info.backend
+        );
+        info!(
+            "Driver: {} {}, vendor {:#06x}, device {:#06x}",
+            adapter_info.driver, adapter_info.driver_info, adapter_info.vendor, adapter_info.device
+        );
+
+        let present_mode = match present_mode {
+            Some(mode) if surface_caps.present_modes.contains(&mode) => mode,
+            // Every surface supports FIFO
+            Some(mode) => {
+                warn!("Present mode {mode:?} isn't supported, using Fifo instead");
+                wgpu::PresentMode::Fifo
+            }
+            None => surface_caps.present_modes[0],
+        };
+
         let (width, height) = (size.0.max(1), size.1.max(1));
+        // Screenshots copy out of the surface texture, where the platform allows it
+        let usage = wgpu::TextureUsages::RENDER_ATTACHMENT
+            | (surface_caps.usages & wgpu::TextureUsages::COPY_SRC);
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage,
             format: surface_format,
-            width: width as u32,
-            height: height as u32,
-            present_mode: surface_caps.present_modes[0],
+            width: width.unsigned_abs(),
+            height: height.unsigned_abs(),
+            present_mode,
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
             desired_maximum_frame_latency: 2,
@@ -125,55 +972,107 @@ impl<'a> State<'a> {
 
         surface.configure(&device, &config);
 
+        let sample_count = supported_sample_count(&adapter, config.format, msaa);
+        if sample_count != msaa {
+            warn!("{msaa}x MSAA isn't supported, using {sample_count}x instead");
+        }
+        let msaa_view = create_msaa_view(&device, &config, sample_count);
+        let depth_view = create_depth_view(&device, &config, sample_count);
+
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Shader"),
             source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
         });
+        let screen_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Screen Buffer"),
+            contents: bytemuck::cast_slice(&[ScreenUniform::new(
+                &config,
+                [0., 0.],
+                view_half_size,
+            )]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let screen_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Screen Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+        let screen_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Screen Bind Group"),
+            layout: &screen_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: screen_buffer.as_entire_binding(),
+            }],
+        });
+
+        let model_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Model Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[],
+            bind_group_layouts: &[&screen_bind_group_layout, &model_bind_group_layout],
             push_constant_ranges: &[],
         });
 
-        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-            label: Some("Default Pipeline"),
-            layout: Some(&pipeline_layout),
-            vertex: VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                compilation_options: PipelineCompilationOptions::default(),
-                buffers: &[Vertex::desc()],
-            },
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            multiview: None,
-            cache: None,
+        let render_pipeline = create_pipeline(
+            &device,
+            &pipeline_layout,
+            &shader,
+            config.format,
+            sample_count,
+            wgpu::PolygonMode::Fill,
+            false,
+        );
+        let instanced_pipeline = create_pipeline(
+            &device,
+            &pipeline_layout,
+            &shader,
+            config.format,
+            sample_count,
+            wgpu::PolygonMode::Fill,
+            true,
+        );
+        let wireframe_pipeline = wireframe.then(|| {
+            create_pipeline(
+                &device,
+                &pipeline_layout,
+                &shader,
+                config.format,
+                sample_count,
+                wgpu::PolygonMode::Line,
+                false,
+            )
         });
 
-        State {
+        let text_renderer = BrushBuilder::using_font_bytes(include_bytes!(
+            "../assets/fonts/DejaVuSansMono-Bold.ttf"
+        ))
+        .expect("Failed to load font")
+        .build(&device, config.width, config.height, config.format);
+
+        Ok(State {
             surface,
             device,
             window,
@@ -181,372 +1080,1514 @@ impl<'a> State<'a> {
             config,
             size: (width, height),
             render_pipeline,
-        }
+            instanced_pipeline,
+            wireframe_pipeline,
+            screen_buffer,
+            screen_bind_group,
+            model_bind_group_layout,
+            view_half_size,
+            sample_count,
+            msaa_view,
+            depth_view,
+            background: Color::BLACK,
+            clear_color: None,
+            text_renderer,
+        })
     }
-}
 
-async fn run() {
-    let mut glfw = glfw::init(fail_on_errors!()).expect("Failed to get glfw instance");
+    fn reconfigure(&self) {
+        self.surface.configure(&self.device, &self.config);
+    }
 
-    let (mut window, _events) = glfw
-        .create_window(1_000, 600, "Pong", glfw::WindowMode::Windowed)
-        .expect("Failed to get window and events handlers.");
+    /// Blocks until everything submitted has finished on the GPU and every buffer mapping has
+    /// called back, so nothing is torn down mid-flight on exit.
+    fn finish_gpu_work(&self) {
+        self.device.poll(wgpu::Maintain::Wait);
+    }
 
-    window.set_key_polling(true);
-    window.make_current();
+    /// Queues the score of `game` at the top of the screen with the match notes under it,
+    /// `message` in the middle, `status` in the bottom-left corner and the `debug` overlay in the
+    /// top-left, all sized relative to the window height. Without a game there's no score.
+    #[allow(clippy::cast_precision_loss)]
+    fn draw_text(
+        &mut self,
+        game: Option<&GameState>,
+        message: Option<&str>,
+        status: Option<&str>,
+        debug: Option<&str>,
+    ) {
+        let (width, height) = (self.config.width as f32, self.config.height as f32);
+        let (score, notes) = game.map_or_else(Default::default, |game| {
+            (score_text(game), match_notes(game))
+        });
+        let score_section = Section::default()
+            .add_text(
+                Text::new(&score)
+                    .with_scale(height * 0.1)
+                    .with_color([1., 1., 1., 1.]),
+            )
+            .with_screen_position((width / 2., height * 0.05))
+            .with_layout(Layout::default().h_align(HorizontalAlign::Center));
+        let notes_section = Section::default()
+            .add_text(
+                Text::new(&notes)
+                    .with_scale(height * 0.04)
+                    .with_color([1., 1., 1., 1.]),
+            )
+            .with_screen_position((width / 2., height * 0.16))
+            .with_layout(Layout::default().h_align(HorizontalAlign::Center));
+        let message_section = Section::default()
+            .add_text(
+                Text::new(message.unwrap_or_default())
+                    .with_scale(height * 0.07)
+                    .with_color([1., 1., 1., 1.]),
+            )
+            .with_screen_position((width / 2., height * 0.6))
+            .with_layout(Layout::default().h_align(HorizontalAlign::Center));
+        let status_section = Section::default()
+            .add_text(
+                Text::new(status.unwrap_or_default())
+                    .with_scale(height * 0.04)
+                    .with_color([1., 1., 1., 1.]),
+            )
+            .with_screen_position((width * 0.02, height * 0.94));
+        let debug_section = Section::default()
+            .add_text(
+                Text::new(debug.unwrap_or_default())
+                    .with_scale(height * 0.03)
+                    .with_color([1., 1., 0.5, 1.]),
+            )
+            .with_screen_position((width * 0.02, height * 0.02));
 
-    let vertices_1 = [
-        Vertex {
-            position: [-0.8, 0.2, 0.0],
-            color: [1., 1., 1.],
-        }, // A
-        Vertex {
-            position: [-0.8, -0.2, 0.0],
-            color: [1., 1., 1.],
-        }, // B
-        Vertex {
-            position: [-0.77, 0.2, 0.0],
-            color: [1., 1., 1.],
-        }, // C
-        Vertex {
-            position: [-0.77, -0.2, 0.0],
-            color: [1., 1., 1.],
-        }, // D
-    ];
-    let indices_1: &[u16] = &[0, 1, 2, 2, 1, 3];
+        self.text_renderer
+            .queue(
+                &self.device,
+                &self.queue,
+                [
+                    &score_section,
+                    &notes_section,
+                    &message_section,
+                    &status_section,
+                    &debug_section,
+                ],
+            )
+            .expect("Failed to queue text");
+    }
 
-    let player_1 = Arc::new(Mutex::new(Player {
-        vertices: Vec::from(vertices_1),
-    }));
+    /// Draws a frame: `drawables` in order, then `outlines` over them when given, saving it as a
+    /// PNG as well when `screenshot` is set.
+    #[allow(clippy::too_many_lines)]
+    fn render(
+        &self,
+        drawables: &[&Drawable],
+        outlines: Option<&Drawable>,
+        screenshot: bool,
+    ) -> Result<(), wgpu::SurfaceError> {
+        let output = self.surface.get_current_texture()?;
+        let view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
 
-    let vertices_2 = [
-        Vertex {
-            position: [0.8, 0.2, 0.0],
-            color: [1., 1., 1.],
-        }, // A
-        Vertex {
-            position: [0.8, -0.2, 0.0],
-            color: [1., 1., 1.],
-        }, // B
-        Vertex {
-            position: [0.77, 0.2, 0.0],
-            color: [1., 1., 1.],
-        }, // C
-        Vertex {
-            position: [0.77, -0.2, 0.0],
-            color: [1., 1., 1.],
-        }, // D
-    ];
-    let indices_2: &[u16] = &[4, 6, 5, 6, 7, 5];
-    let player_2 = Arc::new(Mutex::new(Player {
-        vertices: Vec::from(vertices_2),
-    }));
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder"),
+            });
 
-    let ball = &[
-        Vertex {
-            position: [0.02, 0.02, 0.],
-            color: [1., 1., 1.],
-        },
-        Vertex {
-            position: [-0.02, 0.02, 0.],
-            color: [1., 1., 1.],
-        },
-        Vertex {
-            position: [-0.02, -0.02, 0.],
-            color: [1., 1., 1.],
-        },
-        Vertex {
-            position: [0.02, -0.02, 0.],
-            color: [1., 1., 1.],
-        },
-    ];
+        // With MSAA the geometry goes to the multisampled texture and is resolved into the frame
+        let (target, resolve_target) = match &self.msaa_view {
+            Some(msaa_view) => (msaa_view, Some(&view)),
+            None => (&view, None),
+        };
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(self.clear_color.unwrap_or(self.background)),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
 
-    let ball_indices: &[u16] = &[8, 9, 10, 8, 10, 11];
+        render_pass.set_bind_group(0, &self.screen_bind_group, &[]);
+        for drawable in drawables {
+            let instance_count = if let Some((buffer, count)) = &drawable.instances {
+                render_pass.set_pipeline(&self.instanced_pipeline);
+                render_pass.set_vertex_buffer(1, buffer.slice(..));
+                *count
+            } else {
+                render_pass.set_pipeline(&self.render_pipeline);
+                1
+            };
+            render_pass.set_bind_group(1, &drawable.transform_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, drawable.vertex_buffer.slice(..));
+            render_pass
+                .set_index_buffer(drawable.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            render_pass.draw_indexed(0..drawable.index_count, 0, 0..instance_count);
+        }
+        if let Some(outlines) = outlines {
+            // Without line drawing the outlines are already thin quads the default pipeline fills
+            render_pass.set_pipeline(
+                self.wireframe_pipeline
+                    .as_ref()
+                    .unwrap_or(&self.render_pipeline),
+            );
+            render_pass.set_bind_group(1, &outlines.transform_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, outlines.vertex_buffer.slice(..));
+            render_pass
+                .set_index_buffer(outlines.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            render_pass.draw_indexed(0..outlines.index_count, 0, 0..1);
+        }
+        drop(render_pass);
 
-    let mut ball = Ball {
-        vertices: Vec::from(ball),
-    };
+        let mut text_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Text Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        self.text_renderer.draw(&mut text_pass);
+        drop(text_pass);
 
-    let mut combined_vertices = vec![];
-    combined_vertices.extend_from_slice(&player_1.lock().unwrap().vertices);
-    combined_vertices.extend_from_slice(&player_2.lock().unwrap().vertices);
-    combined_vertices.extend_from_slice(&ball.vertices);
+        let screenshot = if !screenshot {
+            None
+        } else if self.config.usage.contains(wgpu::TextureUsages::COPY_SRC) {
+            Some(Screenshot::capture(
+                &self.device,
+                &mut encoder,
+                &output.texture,
+            ))
+        } else {
+            warn!("This surface doesn't support screenshots");
+            None
+        };
 
-    let mut combined_indices = Vec::from(indices_1);
-    combined_indices.extend_from_slice(indices_2);
-    combined_indices.extend_from_slice(ball_indices);
+        self.queue.submit(std::iter::once(encoder.finish()));
+        if let Some(screenshot) = screenshot {
+            match screenshot.save(&self.device) {
+                Ok(path) => println!("Saved screenshot to {}", path.display()),
+                Err(error) => error!("Failed to save screenshot: {error}"),
+            }
+        }
+        output.present();
 
-    let mut is_w_down = false;
-    let mut is_s_down = false;
-    let mut is_up_down = false;
-    let mut is_down_down = false;
+        Ok(())
+    }
 
-    {
-        let p1 = Arc::clone(&player_1);
-        let p2 = Arc::clone(&player_2);
+    /// Shifts everything drawn by `offset`, in the same units as the vertex positions.
+    fn set_view_offset(&self, offset: [f32; 2]) {
+        self.queue.write_buffer(
+            &self.screen_buffer,
+            0,
+            bytemuck::cast_slice(&[ScreenUniform::new(
+                &self.config,
+                offset,
+                self.view_half_size,
+            )]),
+        );
+    }
 
-        window.set_key_callback(Box::new(
-            move |window: &mut glfw::Window,
-                  key: glfw::Key,
-                  _: i32,
-                  action: glfw::Action,
-                  _: glfw::Modifiers| {
-                let can_move_up = |player: &Arc<Mutex<Player>>| {
-                    player.lock().unwrap().vertices[0].position[1] + 0.05 < 1.
-                };
-                let can_move_down = |player: &Arc<Mutex<Player>>| {
-                    player.lock().unwrap().vertices[3].position[1] - 0.05 > -1.
-                };
+    /// Whether the window is minimized, leaving no surface to draw to.
+    fn is_minimized(&self) -> bool {
+        let (width, height) = self.window.get_framebuffer_size();
+        width <= 0 || height <= 0
+    }
 
-                let player_up = |player: &Arc<Mutex<Player>>| {
-                    player
-                        .lock()
-                        .unwrap()
-                        .vertices
-                        .iter_mut()
-                        .for_each(|vertex| vertex.position[1] += 0.05);
-                };
+    fn resize(&mut self, new_size: (i32, i32)) {
+        let (width, height) = new_size;
+        // Minimized, a zero-sized surface can't be configured, keep the old one until restored
+        if width <= 0 || height <= 0 || (width, height) == self.size {
+            return;
+        }
 
-                let player_down = |player: &Arc<Mutex<Player>>| {
-                    player
-                        .lock()
-                        .unwrap()
-                        .vertices
-                        .iter_mut()
-                        .for_each(|vertex| vertex.position[1] -= 0.05);
-                };
-                if key == glfw::Key::W {
-                    is_w_down = action == Action::Press || action == Action::Repeat;
-                }
-                if key == glfw::Key::S {
-                    is_s_down = action == Action::Press || action == Action::Repeat;
-                }
-                if key == glfw::Key::Up {
-                    is_up_down = action == Action::Press || action == Action::Repeat;
-                }
-                if key == glfw::Key::Down {
-                    is_down_down = action == Action::Press || action == Action::Repeat;
-                }
+        self.size = (width, height);
+        self.config.width = width.unsigned_abs();
+        self.config.height = height.unsigned_abs();
+        self.reconfigure();
+        self.msaa_view = create_msaa_view(&self.device, &self.config, self.sample_count);
+        self.depth_view = create_depth_view(&self.device, &self.config, self.sample_count);
+        self.set_view_offset([0., 0.]);
+        #[allow(clippy::cast_precision_loss)]
+        self.text_renderer.resize_view(
+            self.config.width as f32,
+            self.config.height as f32,
+            &self.queue,
+        );
+    }
+}
 
-                if is_w_down && is_up_down {
-                    if can_move_up(&p1) {
-                        player_up(&p1);
-                    }
-                    if can_move_up(&p1) {
-                        player_up(&p2);
-                    }
-                }
+/// Builds a paddle quad around `center` as top-left, bottom-left, top-right, bottom-right.
+fn make_paddle(
+    center: [f32; 2],
+    half_width: f32,
+    half_height: f32,
+    color: [f32; 3],
+) -> [Vertex; 4] {
+    let [center_x, center_y] = center;
+    let corners = [
+        [center_x - half_width, center_y + half_height],
+        [center_x - half_width, center_y - half_height],
+        [center_x + half_width, center_y + half_height],
+        [center_x + half_width, center_y - half_height],
+    ];
+    std::array::from_fn(|corner| {
+        let [x, y] = corners[corner];
+        Vertex {
+            position: [x, y, 0.],
+            color,
+            uv: QUAD_UVS[corner],
+        }
+    })
+}
 
-                if is_s_down && is_up_down {
-                    if can_move_down(&p1) {
-                        player_down(&p1);
-                    }
-                    if can_move_up(&p2) {
-                        player_up(&p2);
-                    }
-                }
-                if is_w_down && is_down_down {
-                    if can_move_up(&p1) {
-                        player_up(&p1);
-                    }
-                    if can_move_down(&p2) {
-                        player_down(&p2);
-                    }
-                }
+/// Builds a ball quad `half` wide either side of the origin, in the order `BALL_INDICES` expects.
+fn make_ball_vertices(half: f32, color: [f32; 3]) -> [Vertex; 4] {
+    [[1., 1.], [-1., 1.], [-1., -1.], [1., -1.]].map(|[x, y]| Vertex {
+        position: [x * half, y * half, 0.],
+        color,
+        uv: [x, y],
+    })
+}
 
-                if is_s_down && is_down_down {
-                    if can_move_down(&p1) {
-                        player_down(&p1);
-                    }
-                    if can_move_down(&p2) {
-                        player_down(&p2);
-                    }
-                }
-                if is_w_down {
-                    if can_move_up(&p1) {
-                        player_up(&p1);
-                    }
-                }
-                if is_s_down {
-                    if can_move_down(&p1) {
-                        player_down(&p1);
-                    }
-                }
-                if is_up_down {
-                    if can_move_up(&p2) {
-                        player_up(&p2);
-                    }
-                }
-                if is_down_down {
-                    if can_move_down(&p2) {
-                        player_down(&p2);
-                    }
-                }
-            },
-        ));
+/// Creates the F3 overlay's drawable with room for `capacity` collision boxes.
+fn outline_drawable(state: &State, capacity: usize) -> Drawable {
+    let wireframe = state.wireframe_pipeline.is_some();
+    Drawable::new(
+        state,
+        "Collision Boxes",
+        &outline_geometry(&[], capacity, wireframe),
+        &outline_indices(capacity, wireframe),
+    )
+}
+
+/// Creates a drawable for every ball and one for its trail, sized for the match's ball count.
+fn ball_drawables(
+    state: &State,
+    game: &GameState,
+    options: &Options,
+) -> (Vec<Drawable>, Vec<Drawable>) {
+    let trail_indices = quad_indices(u16::try_from(TRAIL_LENGTH).expect("Trail too long"));
+    let empty_trail = trail_geometry(&VecDeque::new(), options.ball_color, options.ball_size);
+
+    game.balls
+        .iter()
+        .map(|ball| {
+            let drawables = (
+                Drawable::new_static(state, "Ball", &unit_quad(&ball.vertices), &BALL_INDICES),
+                Drawable::new(state, "Trail", &empty_trail, &trail_indices),
+            );
+            // Trails only take after the ball's shape when it's a full circle
+            if game.ball_shape == BallShape::Circle {
+                drawables.0.set_roundness(&state.queue, 1.);
+                drawables.1.set_roundness(&state.queue, 1.);
+            } else {
+                drawables.0.set_roundness(&state.queue, CORNER_ROUNDNESS);
+            }
+            drawables
+        })
+        .unzip()
+}
+
+/// Vertices of everything that moves, copied before a physics step to interpolate from while
+/// rendering.
+#[derive(Clone)]
+struct Snapshot {
+    paddle_1: Vec<Vertex>,
+    paddle_2: Vec<Vertex>,
+    balls: Vec<Vec<Vertex>>,
+}
 
-        // Defining the vertex buffers
-        //
+impl Snapshot {
+    fn new(game: &GameState) -> Self {
+        Snapshot {
+            paddle_1: game.player_1.vertices.clone(),
+            paddle_2: game.player_2.vertices.clone(),
+            balls: game
+                .balls
+                .iter()
+                .map(|ball| ball.vertices.clone())
+                .collect(),
+        }
     }
+}
 
-    let state = State::new(&mut window).await;
+/// Lays out both paddles and the balls for a fresh match.
+fn new_game(options: &Options, seed: u64) -> GameState {
+    let half_width = options.aspect.unwrap_or(1.);
+    #[allow(clippy::cast_possible_truncation)]
+    let paddle_x = half_width as f32 - PADDLE_INSET;
+    let player_1 = Player {
+        vertices: Vec::from(make_paddle(
+            [-paddle_x, options.p1_start],
+            PADDLE_HALF_WIDTH,
+            options.paddle_half_height,
+            options.p1_color,
+        )),
+        velocity: 0.,
+    };
+    let player_2 = Player {
+        vertices: Vec::from(make_paddle(
+            [paddle_x, options.p2_start],
+            PADDLE_HALF_WIDTH,
+            options.paddle_half_height,
+            options.p2_color,
+        )),
+        velocity: 0.,
+    };
 
-    let index_buffer = state
-        .device
-        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(combined_indices.as_slice()),
-            usage: BufferUsages::INDEX,
-        });
+    let ball_spawn = make_ball_vertices(options.ball_size, options.ball_color);
 
-    let vertex_buffer = state
-        .device
-        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(combined_vertices.as_slice()),
-            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
-        });
+    let mut game = GameState::new(
+        player_1,
+        player_2,
+        Vec::from(ball_spawn),
+        options.balls,
+        options.ai.then_some(options.difficulty),
+        seed,
+    );
+    game.serve_delay = options.serve_delay;
+    game.target_score = options.target_score;
+    game.power_up_interval = options.power_ups;
+    game.half_width = half_width;
+    game.series = Match::best_of(options.best_of);
+    game.practice_wall = options.wall;
+    game.coop = options.coop;
+    game.serve_side = options.serve_side;
+    game.serve_accel = options.serve_accel;
+    game.ball_speed = options.ball_speed;
+    game.time_limit = options.time_limit;
+    game.ball_shape = options.ball_shape;
+    game
+}
 
-    let sanitize = |player: &Arc<Mutex<Player>>| {
-        // Check top boundary
-        let top_delta = player.lock().unwrap().vertices[0].position[1] - 1.;
-        if top_delta > 0. {
-            player
-                .lock()
-                .unwrap()
-                .vertices
-                .iter_mut()
-                .for_each(|vertex| vertex.position[1] -= top_delta);
-            return; //cannot be breaking both from the top and the bottom considering size of blocks
-        }
-
-        let bottom_delta = -1. - player.lock().unwrap().vertices[3].position[1];
-        if bottom_delta > 0. {
-            player
-                .lock()
-                .unwrap()
-                .vertices
-                .iter_mut()
-                .for_each(|vertex| vertex.position[1] += bottom_delta);
+/// A match for the attract-mode demo, the computer playing both sides a little differently so
+/// rallies don't go on forever.
+fn demo_game(options: &Options, seed: u64) -> GameState {
+    let mut game = new_game(options, seed);
+    game.p1_ai = Some(AiConfig::MEDIUM);
+    game.ai = Some(AiConfig::HARD);
+    game
+}
+
+/// Logs the events worth following with `RUST_LOG=debug`.
+fn log_event(game: &GameState, event: GameEvent) {
+    match event {
+        GameEvent::Scored { side } => debug!(
+            "Point to {side:?}, score {} - {}",
+            game.score.left, game.score.right
+        ),
+        GameEvent::GameOver { winner } => debug!("Game to {winner:?}"),
+        GameEvent::PaddleHit { .. } | GameEvent::WallBounce => {}
+    }
+}
+
+/// The winning player's colour, to fill the screen with once the match is over.
+fn winner_color(game: &GameState, winner: Side) -> Color {
+    let winner = match winner {
+        Side::Left => &game.player_1,
+        Side::Right => &game.player_2,
+    };
+    opaque(winner.vertices[0].color)
+}
+
+/// A clear colour from red, green and blue in `0.0..=1.0`.
+fn opaque([r, g, b]: [f32; 3]) -> Color {
+    Color {
+        r: f64::from(r),
+        g: f64::from(g),
+        b: f64::from(b),
+        a: 1.,
+    }
+}
+
+/// The score line at the top of the screen, a rally count instead when there's no opponent.
+fn score_text(game: &GameState) -> String {
+    if game.practice_wall {
+        format!("Rally {}   Best {}", game.rally, game.best_rally)
+    } else if game.coop {
+        format!("Rally {}", game.rally)
+    } else {
+        format!("{}   {}", game.score.left, game.score.right)
+    }
+}
+
+/// The lines under the score: games won in a series, overtime and the time left.
+fn match_notes(game: &GameState) -> String {
+    let mut notes = Vec::new();
+    // Games won only mean something in a series
+    if game.series.games_to_win > 1 {
+        notes.push(format!(
+            "Games {} - {}",
+            game.series.left_games, game.series.right_games
+        ));
+    }
+    match game.overtime() {
+        Some(Overtime::Deuce) => notes.push("Deuce".to_string()),
+        Some(Overtime::Advantage(Side::Left)) => notes.push("Advantage P1".to_string()),
+        Some(Overtime::Advantage(Side::Right)) => notes.push("Advantage P2".to_string()),
+        Some(Overtime::SuddenDeath) => notes.push("Sudden Death".to_string()),
+        None => {}
+    }
+    // Counted down in whole seconds, rounding up so 0:00 means time's up
+    if let Some(time_left) = game.time_left().filter(|&left| left > 0.) {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let seconds = time_left.ceil() as u64;
+        notes.push(format!("{}:{:02}", seconds / 60, seconds % 60));
+    }
+    notes.join("\n")
+}
+
+/// The live numbers shown by the backtick debug overlay.
+fn debug_text(game: &GameState, frame_time: f64) -> String {
+    let mut lines = vec![format!(
+        "{:.0} FPS ({:.2} ms)",
+        1. / frame_time,
+        frame_time * 1_000.
+    )];
+    for (index, ball) in game.balls.iter().enumerate() {
+        let center = ball.center();
+        lines.push(format!(
+            "Ball {}: ({:.3}, {:.3}) speed {:.3} heading {:.1} deg spin {:.2}",
+            index + 1,
+            center.x,
+            center.y,
+            ball.velocity,
+            ball.velocity_direction.val().to_degrees(),
+            ball.spin
+        ));
+    }
+    for (name, player) in [("P1", &game.player_1), ("P2", &game.player_2)] {
+        lines.push(format!(
+            "{name}: y {:.3} velocity {:.3}",
+            player.center().y,
+            player.velocity
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Writes the inputs recorded with `--record`, if any.
+fn save_recording(options: &Options, recording: Option<&Replay>) {
+    if let (Some(path), Some(recording)) = (&options.record, recording) {
+        match recording.save(path) {
+            Ok(()) => println!("Saved replay to {}", path.display()),
+            Err(error) => error!("Failed to save replay to {}: {error}", path.display()),
         }
+    }
+}
+
+/// Plays a match for `seconds` of simulated time without a window, player 1 tracking the ball
+/// (or following `playback`) and the computer driving player 2, then prints the final score.
+/// With `--demo` the computer plays both sides, as in the attract mode.
+fn run_headless(options: &Options, seconds: u32, seed: u64, playback: Option<Replay>) {
+    let mut game = if options.demo {
+        demo_game(options, seed)
+    } else {
+        let mut game = new_game(options, seed);
+        game.ai = Some(options.difficulty);
+        game
     };
-    let coin_toss = |probability: f64| rand::random_bool(probability);
+    game.serve();
 
-    let to_player_1 = coin_toss(0.5);
+    let mut recording = options.record.as_ref().map(|_| Replay::new(seed));
+    let mut playback = playback.map(|replay| replay.inputs.into_iter());
+    let mut input_delay = InputDelay::new(options.input_delay);
 
-    let ball_centroid = [0., 0.];
-    let velocity = if to_player_1 {
-        [-0.0051, 0.]
+    let mut elapsed = 0.;
+    while elapsed < f64::from(seconds) {
+        // Chase whichever ball is closest to the left edge
+        let ball_y = game
+            .balls
+            .iter()
+            .map(Ball::center)
+            .min_by(|a, b| a.x.total_cmp(&b.x))
+            .map_or(0., |center| center.y);
+        let paddle_y =
+            game.player_1.vertices[0].position[1] + game.player_1.vertices[1].position[1];
+        // Aim a little below the ball so returns come off the paddle at an angle
+        let paddle_y = f64::from(paddle_y / 2.) + 0.1;
+        let input = match &mut playback {
+            Some(inputs) => inputs.next().unwrap_or_default(),
+            None => InputState {
+                p1_up: ball_y > paddle_y + 0.05,
+                p1_down: ball_y < paddle_y - 0.05,
+                ..InputState::default()
+            },
+        };
+        if let Some(recording) = &mut recording {
+            recording.inputs.push(input);
+        }
+        let input = if options.p2_invert {
+            input.with_p2_inverted()
+        } else {
+            input
+        };
+        let input = input_delay.delay(input);
+
+        for event in game.update(FIXED_DT, &input) {
+            log_event(&game, event);
+        }
+        elapsed += FIXED_DT;
+        if game.winner().is_some() && game.finish_game().is_some() {
+            break;
+        }
+    }
+
+    if game.coop {
+        println!("Final rally after {elapsed:.1}s: {}", game.rally);
     } else {
-        [0.0051, 0.]
+        println!(
+            "Final score after {elapsed:.1}s: {} - {}",
+            game.score.left, game.score.right
+        );
+    }
+    if game.series.games_to_win > 1 {
+        println!(
+            "Games: {} - {}",
+            game.series.left_games, game.series.right_games
+        );
+    }
+    save_recording(options, recording.as_ref());
+}
+
+/// Writes the frame times collected with `--profile`, if any, and prints their percentiles.
+fn save_profile(options: &Options, profile: Option<&FrameProfile>) {
+    if let (Some(path), Some(profile)) = (&options.profile, profile) {
+        println!("{}", profile.summary());
+        match profile.save(path) {
+            Ok(()) => println!("Saved frame times to {}", path.display()),
+            Err(error) => error!("Failed to save frame times to {}: {error}", path.display()),
+        }
+    }
+}
+
+/// Where a window sat before going fullscreen.
+struct WindowPlacement {
+    position: (i32, i32),
+    size: (i32, i32),
+}
+
+/// Switches between a window and fullscreen on the primary monitor at its current video mode.
+/// `windowed_placement` holds the window's position and size while it is fullscreen.
+fn toggle_fullscreen(
+    glfw: &mut glfw::Glfw,
+    window: &mut Window,
+    windowed_placement: &mut Option<WindowPlacement>,
+) {
+    if let Some(WindowPlacement {
+        position: (x, y),
+        size: (width, height),
+    }) = windowed_placement.take()
+    {
+        window.set_monitor(
+            glfw::WindowMode::Windowed,
+            x,
+            y,
+            width.unsigned_abs(),
+            height.unsigned_abs(),
+            None,
+        );
+        return;
+    }
+
+    glfw.with_primary_monitor(|_, monitor| {
+        let Some(monitor) = monitor else {
+            warn!("No monitor to go fullscreen on");
+            return;
+        };
+        let Some(mode) = monitor.get_video_mode() else {
+            warn!("Failed to read the monitor's video mode");
+            return;
+        };
+
+        *windowed_placement = Some(WindowPlacement {
+            position: window.get_pos(),
+            size: window.get_size(),
+        });
+        window.set_monitor(
+            glfw::WindowMode::FullScreen(monitor),
+            0,
+            0,
+            mode.width,
+            mode.height,
+            Some(mode.refresh_rate),
+        );
+    });
+}
+
+/// The options menu's view of the match as it's being played.
+fn live_settings(game: &GameState, options: &Options) -> LiveSettings {
+    LiveSettings {
+        ball_speed: game.ball_speed,
+        paddle_half_height: options.paddle_half_height,
+        difficulty: game.ai.unwrap_or(options.difficulty),
+    }
+}
+
+/// Puts settings confirmed in the options menu into effect, and keeps them for later games.
+fn apply_live_settings(game: &mut GameState, options: &mut Options, settings: LiveSettings) {
+    game.ball_speed = settings.ball_speed;
+    game.set_paddle_half_height(f64::from(settings.paddle_half_height));
+    if game.ai.is_some() {
+        game.ai = Some(settings.difficulty);
+    }
+    options.ball_speed = settings.ball_speed;
+    options.paddle_half_height = settings.paddle_half_height;
+    options.difficulty = settings.difficulty;
+}
+
+/// The options menu's meaning for `key`, if it has one.
+fn menu_key(key: glfw::Key) -> Option<MenuKey> {
+    match key {
+        glfw::Key::Up => Some(MenuKey::Up),
+        glfw::Key::Down => Some(MenuKey::Down),
+        glfw::Key::Left => Some(MenuKey::Left),
+        glfw::Key::Right => Some(MenuKey::Right),
+        glfw::Key::Enter | glfw::Key::KpEnter => Some(MenuKey::Confirm),
+        glfw::Key::Escape => Some(MenuKey::Back),
+        _ => None,
+    }
+}
+
+#[allow(clippy::too_many_lines)]
+fn run(mut options: Options, seed: u64, playback: Option<Replay>) -> Result<(), GameError> {
+    let mut glfw = glfw::init(log_errors!())?;
+
+    let (mut window, events) = glfw
+        .create_window(
+            options.window_size.0,
+            options.window_size.1,
+            "Pong",
+            glfw::WindowMode::Windowed,
+        )
+        .ok_or(GameError::Window)?;
+
+    window.set_key_polling(true);
+    window.set_framebuffer_size_polling(true);
+    window.set_focus_polling(true);
+    window.make_current();
+
+    // Game Init
+    let mut game = if options.demo {
+        // Only the real match is seeded, the demo just needs to look different every time
+        let mut demo = demo_game(&options, rand::random());
+        demo.serve();
+        demo
+    } else {
+        new_game(&options, seed)
+    };
+    let mut recording = options.record.as_ref().map(|_| Replay::new(seed));
+    // Rematches draw their seeds from the first one, so a `--seed` session plays out the same
+    // through every restart
+    let mut rematch_seeds = StdRng::seed_from_u64(seed);
+    let mut profile = options.profile.as_ref().map(|_| FrameProfile::new());
+    let mut playback = playback.map(|replay| replay.inputs.into_iter());
+    let mut input_delay = InputDelay::new(options.input_delay);
+    let mut trails = vec![VecDeque::with_capacity(TRAIL_LENGTH); game.balls.len()];
+
+    let input = Arc::new(Mutex::new(InputState::default()));
+
+    {
+        let input = Arc::clone(&input);
+        let bindings = options.key_bindings;
+
+        window.set_key_callback(Box::new(
+            move |_: &mut glfw::Window,
+                  key: glfw::Key,
+                  _: i32,
+                  action: glfw::Action,
+                  _: glfw::Modifiers| {
+                // Repeats only come at the OS key-repeat rate, and the held flags already know
+                // the key is down. Paddles move by time held, so a repeat must never move them.
+                if action == Action::Repeat {
+                    return;
+                }
+                let is_down = action == Action::Press;
+                let mut input = input.lock().unwrap();
+                if action == Action::Press {
+                    input.any_key = true;
+                    input.menu_key = menu_key(key);
+                }
+                match key {
+                    key if key == bindings.p1_up => input.p1_up = is_down,
+                    key if key == bindings.p1_down => input.p1_down = is_down,
+                    key if key == bindings.p2_up => input.p2_up = is_down,
+                    key if key == bindings.p2_down => input.p2_down = is_down,
+                    glfw::Key::Space => input.charge_serve = is_down,
+                    glfw::Key::P if action == Action::Press => input.toggle_pause = true,
+                    glfw::Key::F5 if action == Action::Press => input.save_requested = true,
+                    glfw::Key::F9 if action == Action::Press => input.load_requested = true,
+                    glfw::Key::F11 if action == Action::Press => input.toggle_fullscreen = true,
+                    glfw::Key::F12 if action == Action::Press => input.screenshot_requested = true,
+                    glfw::Key::LeftBracket if action == Action::Press => input.slow_down = true,
+                    glfw::Key::RightBracket if action == Action::Press => input.speed_up = true,
+                    glfw::Key::GraveAccent if action == Action::Press => input.toggle_debug = true,
+                    glfw::Key::F3 if action == Action::Press => input.toggle_boxes = true,
+                    glfw::Key::O if action == Action::Press => input.open_options = true,
+                    glfw::Key::R if action == Action::Press => input.restart = true,
+                    _ => {}
+                }
+            },
+        ));
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    let mut view_half_size = match options.aspect {
+        Some(aspect) => [aspect as f32, 1. + WALL_THICKNESS],
+        None => [1., 1.],
     };
-    let acceleration = [0., 0.];
+    // Make room for the practice wall just past the right edge
+    if options.wall {
+        view_half_size[0] += WALL_THICKNESS;
+    }
+    // Finding a GPU and opening a device can take a moment, with nothing able to draw until
+    // they're ready. The title says what's going on, and events keep being handled so the
+    // window doesn't look frozen.
+    window.set_title("Pong - Loading...");
+    let mut state = State::new(
+        &mut glfw,
+        &mut window,
+        options.present_mode,
+        options.msaa,
+        view_half_size,
+    )?;
+    state.background = opaque(options.background);
+    // Without Immediate the frames wait for vsync anyway, so keep blending for smooth motion
+    if options.low_latency && state.config.present_mode != wgpu::PresentMode::Immediate {
+        options.low_latency = false;
+    }
+    // The rest of the setup, audio and gamepads included, happens behind a loading frame
+    state.draw_text(None, Some("Loading..."), None, None);
+    if let Err(error) = state.render(&[], None, false) {
+        debug!("Failed to draw the loading frame: {error}");
+    }
+
+    let paddle_indices = quad_indices(1);
+    let paddle_1 = Drawable::new_static(
+        &state,
+        "Player 1",
+        &unit_quad(&game.player_1.vertices),
+        &paddle_indices,
+    );
+    let paddle_2 = Drawable::new_static(
+        &state,
+        "Player 2",
+        &unit_quad(&game.player_2.vertices),
+        &paddle_indices,
+    );
+    paddle_1.set_roundness(&state.queue, CORNER_ROUNDNESS);
+    paddle_2.set_roundness(&state.queue, CORNER_ROUNDNESS);
+    let (mut balls, mut trail_drawables) = ball_drawables(&state, &game, &options);
+    let charge_bar = Drawable::new(
+        &state,
+        "Serve Charge",
+        &charge_bar_geometry(0.),
+        &quad_indices(1),
+    );
+    // Room for both paddles, every ball served and one split off by each power-up on the field
+    let mut outline_capacity = 2 + options.balls + MAX_POWER_UPS;
+    let mut outlines = outline_drawable(&state, outline_capacity);
+    let mut shown_power_ups = power_up_geometry(&[]);
+    // What each trail buffer last had written to it, `None` until the first write
+    let mut shown_trails: Vec<Option<Vec<Vertex>>> = Vec::new();
+    let power_ups = Drawable::new(
+        &state,
+        "Power-ups",
+        &shown_power_ups,
+        &quad_indices(u16::try_from(MAX_POWER_UPS).expect("Too many power-ups")),
+    );
+    #[allow(clippy::cast_possible_truncation)]
+    let walls = options.aspect.map(|aspect| {
+        Drawable::new_static(
+            &state,
+            "Walls",
+            &wall_geometry(aspect as f32),
+            &quad_indices(2),
+        )
+    });
+    #[allow(clippy::cast_possible_truncation)]
+    let practice_wall = options.wall.then(|| {
+        Drawable::new_static(
+            &state,
+            "Practice Wall",
+            &practice_wall_geometry(options.aspect.unwrap_or(1.) as f32),
+            &quad_indices(1),
+        )
+    });
+    let net = Drawable::new_static(&state, "Net", &net_dash_geometry(), &quad_indices(1))
+        .with_instances(&state, &net_dash_offsets());
 
-    let mut ball_physics = BallPhysics {
-        position: ball_centroid,
-        velocity,
-        acceleration,
+    #[cfg(feature = "gamepad")]
+    let mut gamepads = gamepad::Gamepads::new();
+    #[cfg(feature = "audio")]
+    let audio = audio::Audio::new();
+
+    let mut phase = if options.demo {
+        GamePhase::Demo
+    } else {
+        GamePhase::Menu
     };
-    // Game Init
-    // if to_player_1 {
-    //     ball.velocity = 0.03;
-    // } else {
-    //     ball.velocity = 0.03;
-    // }
+    // Position and size to go back to when leaving fullscreen
+    let mut windowed_placement = None;
+    let mut paused = false;
+    // Only ever open while paused
+    let mut options_menu: Option<PauseMenu> = None;
+    let mut accumulator = 0.;
+    // Halvings, when negative, or doublings of the speed, counted rather than multiplied out so
+    // they always lead back to exactly normal speed
+    let mut speed_steps: i32 = 0;
+    let mut previous = Snapshot::new(&game);
+    // A ring of the latest steps of the match, handed over to the replay when a point is scored
+    let mut history = VecDeque::with_capacity(REPLAY_STEPS);
+    let mut replay_frames: Vec<Snapshot> = Vec::new();
+    // Seconds into the replay, measured in game time
+    let mut replay_time = 0.;
+    // How far the view can currently jump, decaying back to 0 after a hard hit
+    let mut shake: f64 = 0.;
+
+    let mut host = options
+        .host
+        .map(net::Host::bind)
+        .transpose()
+        .map_err(GameError::Network)?;
+    let mut client = options
+        .connect
+        .as_deref()
+        .map(net::Client::connect)
+        .transpose()
+        .map_err(GameError::Network)?;
+    state.window.set_title("Pong");
+    // Keys the remote player last reported holding
+    let mut remote_input = InputState::default();
+    // When the client got the newest snapshot, and how long after the one before it
+    let mut last_snapshot = 0.;
+    let mut snapshot_interval = FIXED_DT;
+
     // Game Loop
-    let mut past_time = std::time::Instant::now();
+    // Frames, seconds, longest frame and bytes uploaded since the title last showed the frame rate
+    let mut fps_frames = 0u32;
+    let mut fps_elapsed = 0.;
+    let mut fps_max_frame_time: f64 = 0.;
+    let mut fps_uploaded = 0usize;
+    let mut show_debug = false;
+    let mut show_boxes = false;
+    let mut frame_time = FIXED_DT;
+    // Summed input-to-present times and their count since the last `--vsync off` latency log
+    let mut latency_total = 0.;
+    let mut latency_frames = 0u32;
+
+    let mut last_time = glfw.get_time();
     while !state.window.should_close() {
+        let now = glfw.get_time();
+        let dt = now - last_time;
+        last_time = now;
+
+        if options.show_fps {
+            fps_frames += 1;
+            fps_elapsed += dt;
+            fps_max_frame_time = fps_max_frame_time.max(dt);
+            if fps_elapsed >= 1. {
+                let fps = f64::from(fps_frames) / fps_elapsed;
+                // How much the frames write to the GPU, to see unchanged geometry staying put
+                #[allow(clippy::cast_precision_loss)]
+                let uploaded = fps_uploaded as f64 / f64::from(fps_frames);
+                state.window.set_title(&format!(
+                    "Pong - {fps:.0} FPS (max {:.1} ms, {uploaded:.0} B uploaded per frame)",
+                    fps_max_frame_time * 1_000.
+                ));
+                (fps_frames, fps_elapsed, fps_max_frame_time, fps_uploaded) = (0, 0., 0., 0);
+            }
+        }
         glfw.poll_events();
-        let delta_t = std::time::Instant::now() - past_time;
-        past_time = std::time::Instant::now();
+        let mut polled = glfw.get_time();
+        for (_, event) in glfw::flush_messages(&events) {
+            match event {
+                glfw::WindowEvent::FramebufferSize(width, height) => state.resize((width, height)),
+                // Keys released while another window has focus never send a release event
+                glfw::WindowEvent::Focus(false) => {
+                    *input.lock().unwrap() = InputState::default();
+                    // Stays paused on return until the player unpauses
+                    if phase == GamePhase::Playing && client.is_none() && !paused {
+                        paused = true;
+                        state.clear_color = Some(PAUSED_COLOR);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let any_key = std::mem::take(&mut input.lock().unwrap().any_key);
+        if std::mem::take(&mut input.lock().unwrap().toggle_debug) {
+            show_debug = !show_debug;
+        }
+        if std::mem::take(&mut input.lock().unwrap().toggle_boxes) {
+            show_boxes = !show_boxes;
+        }
+        // Smoothed so the overlay's numbers can be read
+        frame_time += (dt - frame_time) * 0.05;
+        if std::mem::take(&mut input.lock().unwrap().toggle_pause)
+            && phase == GamePhase::Playing
+            && client.is_none()
+        {
+            paused = !paused;
+            state.clear_color = paused.then_some(PAUSED_COLOR);
+        }
+        let (open_options, menu_key) = {
+            let mut input = input.lock().unwrap();
+            (
+                std::mem::take(&mut input.open_options),
+                input.menu_key.take(),
+            )
+        };
+        if !paused {
+            options_menu = None;
+        } else if let Some(menu) = &mut options_menu {
+            match menu_key.map(|key| menu.handle(key)) {
+                Some(MenuOutcome::Apply(settings)) => {
+                    apply_live_settings(&mut game, &mut options, settings);
+                    options_menu = None;
+                }
+                Some(MenuOutcome::Cancel) => options_menu = None,
+                Some(MenuOutcome::Open) | None => {}
+            }
+        } else if open_options {
+            options_menu = Some(PauseMenu::open(live_settings(&game, &options)));
+        }
 
-        // Update parameters
-        ball_physics
-            .position
-            .iter_mut()
-            .zip(ball_physics.velocity)
-            .for_each(|(pos, vel)| *pos += vel * delta_t.as_secs_f32());
+        {
+            let mut input = input.lock().unwrap();
+            if std::mem::take(&mut input.slow_down) && time_scale(speed_steps) > MIN_TIME_SCALE {
+                speed_steps -= 1;
+            }
+            if std::mem::take(&mut input.speed_up) && time_scale(speed_steps) < MAX_TIME_SCALE {
+                speed_steps += 1;
+            }
+        }
 
-        ball_physics
-            .velocity
-            .iter_mut()
-            .zip(ball_physics.acceleration)
-            .for_each(|(vel, acc)| *vel += acc * delta_t.as_secs_f32());
+        if std::mem::take(&mut input.lock().unwrap().toggle_fullscreen) {
+            toggle_fullscreen(&mut glfw, state.window, &mut windowed_placement);
+            let size = state.window.get_framebuffer_size();
+            state.resize(size);
+        }
 
-        // Bounce on top
-        if ball_physics.position[1] > 1. && ball_physics.velocity[1] > 0. {
-            ball_physics.velocity[1] *= -1.;
-            ball_physics.position[1] -= 0.02;
+        let (save_requested, load_requested) = {
+            let mut input = input.lock().unwrap();
+            (
+                std::mem::take(&mut input.save_requested),
+                std::mem::take(&mut input.load_requested),
+            )
+        };
+        if save_requested {
+            match physics::save_state(Path::new(SAVE_PATH), &game) {
+                Ok(()) => println!("Saved game to {SAVE_PATH}"),
+                Err(error) => error!("Failed to save game to {SAVE_PATH}: {error}"),
+            }
+        }
+        if load_requested {
+            match physics::load_state(Path::new(SAVE_PATH)) {
+                Ok(loaded) => {
+                    game = loaded;
+                    // The loaded balls are already in flight, so there's nothing to serve
+                    if matches!(phase, GamePhase::Demo | GamePhase::Menu) {
+                        phase = GamePhase::Playing;
+                    }
+                    previous = Snapshot::new(&game);
+                    history.clear();
+                    trails = vec![VecDeque::with_capacity(TRAIL_LENGTH); game.balls.len()];
+                    // The save may have a different number of balls
+                    (balls, trail_drawables) = ball_drawables(&state, &game, &options);
+                    shown_trails.clear();
+                    println!("Loaded game from {SAVE_PATH}");
+                }
+                Err(error) => error!("Failed to load game from {SAVE_PATH}: {error}"),
+            }
         }
 
-        if ball_physics.position[1] < -1. && ball_physics.velocity[1] < 0. {
-            ball_physics.velocity[1] *= -1.;
-            ball_physics.position[1] += 0.02;
+        // The host decides when the match restarts, and a replay plays out as it was recorded
+        if std::mem::take(&mut input.lock().unwrap().restart)
+            && client.is_none()
+            && playback.is_none()
+        {
+            // A recording started over keeps its seed so it still replays exactly, otherwise every
+            // rematch gets serves of its own
+            let match_seed = if recording.is_some() {
+                seed
+            } else {
+                rematch_seeds.random()
+            };
+            println!("Seed: {match_seed}");
+            game = new_game(&options, match_seed);
+            game.serve();
+            speed_steps = 0;
+            if let Some(recording) = &mut recording {
+                recording.inputs.clear();
+            }
+            phase = GamePhase::Playing;
+            paused = false;
+            options_menu = None;
+            state.clear_color = None;
+            shake = 0.;
+            previous = Snapshot::new(&game);
+            history.clear();
+            replay_frames.clear();
+            trails = vec![VecDeque::with_capacity(TRAIL_LENGTH); game.balls.len()];
+            // Power-ups may have split off extra balls
+            (balls, trail_drawables) = ball_drawables(&state, &game, &options);
+            shown_trails.clear();
         }
 
-        ball.vertices.iter_mut().for_each(|vertex| {
-            vertex.position[0] += ball_physics.position[0];
-            vertex.position[1] += ball_physics.position[1];
-        });
+        if let Some(host) = &mut host {
+            if let Some(remote) = host.receive_input() {
+                remote_input = remote;
+            }
+        }
+        if let Some(client) = &mut client {
+            #[allow(unused_mut)]
+            let mut held = *input.lock().unwrap();
+            #[cfg(feature = "gamepad")]
+            if let Some(gamepads) = &mut gamepads {
+                gamepads.apply(&mut held);
+            }
+            client.send_input(&held);
 
-        sanitize(&player_1);
-        sanitize(&player_2);
+            if let Some((host_phase, received)) = client.receive_state() {
+                previous = Snapshot::new(&game);
+                let score = game.score;
+                game = received;
+                snapshot_interval = now - last_snapshot;
+                last_snapshot = now;
 
-        // Checks collision
-        if ball_physics.position < &player_1.lock().unwrap().vertices {}
+                if game.balls.len() != balls.len() {
+                    previous = Snapshot::new(&game);
+                    trails = vec![VecDeque::with_capacity(TRAIL_LENGTH); game.balls.len()];
+                    (balls, trail_drawables) = ball_drawables(&state, &game, &options);
+                    shown_trails.clear();
+                } else if game.score != score {
+                    previous = Snapshot::new(&game);
+                    trails.iter_mut().for_each(VecDeque::clear);
+                }
+                for (trail, ball) in trails.iter_mut().zip(&game.balls) {
+                    trail.push_front(ball.center());
+                    trail.truncate(TRAIL_LENGTH);
+                }
 
-        // Render
-        let mut new_vertices = vec![];
-        new_vertices.extend_from_slice(&player_1.lock().unwrap().vertices);
-        new_vertices.extend_from_slice(&player_2.lock().unwrap().vertices);
-        new_vertices.extend_from_slice(&ball.vertices);
+                phase = match host_phase {
+                    // The client keeps no history to replay, so it shows the match paused instead
+                    GamePhase::Replay => GamePhase::Playing,
+                    host_phase => host_phase,
+                };
+                // Nobody wins a co-op match
+                state.clear_color = game
+                    .series
+                    .winner()
+                    .filter(|_| phase == GamePhase::GameOver && !game.coop)
+                    .map(|winner| winner_color(&game, winner));
+            }
+        }
 
-        state.queue.write_buffer(
-            &vertex_buffer,
-            0,
-            bytemuck::cast_slice(new_vertices.as_slice()),
+        match phase {
+            // The host runs the match, the client only shows it
+            _ if client.is_some() => {}
+            GamePhase::Menu => {
+                if state.window.get_key(glfw::Key::Space) == Action::Press {
+                    game.serve();
+                    phase = GamePhase::Playing;
+                } else if state.window.get_key(glfw::Key::Escape) == Action::Press {
+                    state.window.set_should_close(true);
+                }
+            }
+            GamePhase::Demo if any_key => {
+                game = new_game(&options, seed);
+                game.serve();
+                phase = GamePhase::Playing;
+                previous = Snapshot::new(&game);
+                trails.iter_mut().for_each(VecDeque::clear);
+            }
+            GamePhase::Playing if paused => {}
+            GamePhase::Demo | GamePhase::Playing => {
+                // Read the keys again as late as possible, nothing but the steps below and the
+                // frame itself stand between them and the screen
+                if options.low_latency {
+                    glfw.poll_events();
+                    polled = glfw.get_time();
+                }
+                #[allow(unused_mut)]
+                let mut held = *input.lock().unwrap();
+                #[cfg(feature = "gamepad")]
+                if let Some(gamepads) = &mut gamepads {
+                    gamepads.apply(&mut held);
+                }
+
+                accumulator += dt * time_scale(speed_steps);
+                let mut events = Vec::new();
+                while matches!(phase, GamePhase::Demo | GamePhase::Playing)
+                    && accumulator >= FIXED_DT
+                {
+                    accumulator -= FIXED_DT;
+                    previous = Snapshot::new(&game);
+                    if phase == GamePhase::Playing {
+                        if history.len() == REPLAY_STEPS {
+                            history.pop_front();
+                        }
+                        history.push_back(previous.clone());
+                    }
+
+                    let mut held = held;
+                    if host.is_some() {
+                        // Either pair of paddle keys on the remote end moves player 2
+                        held.p2_up = remote_input.p1_up || remote_input.p2_up;
+                        held.p2_down = remote_input.p1_down || remote_input.p2_down;
+                    }
+                    // The demo neither plays back nor records, that's for the real match
+                    if phase == GamePhase::Playing {
+                        if let Some(inputs) = &mut playback {
+                            held = inputs.next().unwrap_or_default();
+                        }
+                        if let Some(recording) = &mut recording {
+                            recording.inputs.push(held);
+                        }
+                    }
+                    // Recordings keep the keys as pressed, so play them back with the same flag
+                    if options.p2_invert {
+                        held = held.with_p2_inverted();
+                    }
+                    let held = input_delay.delay(held);
+
+                    let score = game.score;
+                    let step_events = game.update(FIXED_DT, &held);
+                    for &event in &step_events {
+                        log_event(&game, event);
+                    }
+                    events.extend(step_events);
+                    if game.balls.len() != balls.len() {
+                        // A power-up split a ball off, or a split-off one went away
+                        previous = Snapshot::new(&game);
+                        // The recorded steps no longer line up with the ball drawables
+                        history.clear();
+                        trails = vec![VecDeque::with_capacity(TRAIL_LENGTH); game.balls.len()];
+                        (balls, trail_drawables) = ball_drawables(&state, &game, &options);
+                        shown_trails.clear();
+                    } else if game.score != score {
+                        // A ball was re-served, don't blend it across the field
+                        previous = Snapshot::new(&game);
+                        trails.iter_mut().for_each(VecDeque::clear);
+                    }
+
+                    for (trail, ball) in trails.iter_mut().zip(&game.balls) {
+                        trail.push_front(ball.center());
+                        trail.truncate(TRAIL_LENGTH);
+                    }
+
+                    if game.winner().is_some() && phase == GamePhase::Demo {
+                        // Attract mode loops forever, starting every match from 0 - 0
+                        game = demo_game(&options, rand::random());
+                        game.serve();
+                        previous = Snapshot::new(&game);
+                        trails.iter_mut().for_each(VecDeque::clear);
+                    } else if game.winner().is_some() {
+                        if let Some(winner) = game.finish_game() {
+                            state.clear_color = (!game.coop).then(|| winner_color(&game, winner));
+                            phase = GamePhase::GameOver;
+                        } else {
+                            // On to the next game of the series
+                            previous = Snapshot::new(&game);
+                            trails.iter_mut().for_each(VecDeque::clear);
+                        }
+                    }
+
+                    if game.score != score && phase == GamePhase::Playing && history.len() >= 2 {
+                        replay_frames = history.drain(..).collect();
+                        replay_time = 0.;
+                        phase = GamePhase::Replay;
+                    }
+                }
+
+                // Like an arcade cabinet, the demo plays silently
+                #[cfg(feature = "audio")]
+                if let Some(audio) = audio.as_ref().filter(|_| phase == GamePhase::Playing) {
+                    for event in &events {
+                        match event {
+                            GameEvent::PaddleHit { .. } => audio.play_hit(),
+                            GameEvent::WallBounce => audio.play_wall(),
+                            GameEvent::Scored { .. } => audio.play_score(),
+                            GameEvent::GameOver { .. } => {}
+                        }
+                    }
+                }
+
+                let hard_hit = events.iter().any(|event| {
+                    matches!(event, GameEvent::PaddleHit { speed, .. } if *speed >= HARD_HIT_SPEED)
+                });
+                if hard_hit {
+                    shake = SHAKE_STRENGTH;
+                }
+            }
+            GamePhase::Replay => {
+                replay_time += dt * REPLAY_SPEED;
+                #[allow(clippy::cast_precision_loss)]
+                let finished = replay_time / FIXED_DT >= (replay_frames.len() - 1) as f64;
+                if finished || state.window.get_key(glfw::Key::Space) == Action::Press {
+                    replay_frames.clear();
+                    phase = GamePhase::Playing;
+                }
+            }
+            GamePhase::GameOver => {
+                if state.window.get_key(glfw::Key::Escape) == Action::Press {
+                    state.window.set_should_close(true);
+                }
+            }
+        }
+
+        if let Some(host) = &mut host {
+            host.send_state(&game, phase);
+        }
+
+        // Only the view shakes, the game itself never moves
+        shake *= (-dt / SHAKE_DECAY_TIME).exp();
+        #[allow(clippy::cast_possible_truncation)]
+        let jitter = (shake * rand::random_range(-1. ..=1.)) as f32;
+        state.set_view_offset([0., jitter]);
+
+        // Render
+        // Draw between the last two physics steps, so motion stays smooth at any frame rate
+        let blend = if options.low_latency {
+            // Blending would show the paddles where they were a step ago
+            1.
+        } else if client.is_some() {
+            // The client blends between snapshots as they arrive instead of physics steps
+            ((now - last_snapshot) / snapshot_interval).min(1.)
+        } else {
+            accumulator / FIXED_DT
+        };
+        let current;
+        let (from, to, blend) = if phase == GamePhase::Replay {
+            // Play back the recorded steps, blending between them just like live ones
+            let position = replay_time / FIXED_DT;
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let index = (position as usize).min(replay_frames.len() - 2);
+            (
+                &replay_frames[index],
+                &replay_frames[index + 1],
+                position.fract(),
+            )
+        } else {
+            current = Snapshot::new(&game);
+            (&previous, &current, blend)
+        };
+        let mut uploaded = paddle_1.set_transform(
+            &state.queue,
+            &quad_transform(&from.paddle_1, &to.paddle_1, blend),
+        );
+        uploaded += paddle_2.set_transform(
+            &state.queue,
+            &quad_transform(&from.paddle_2, &to.paddle_2, blend),
         );
+        for ((drawable, from), to) in balls.iter().zip(&from.balls).zip(&to.balls) {
+            uploaded += drawable.set_transform(&state.queue, &quad_transform(from, to, blend));
+        }
+        // Power-ups sit still until one spawns or is collected, so most frames have nothing to write
+        let power_up_vertices = power_up_geometry(&game.power_ups);
+        if power_up_vertices != shown_power_ups {
+            uploaded += power_ups.update(&state.queue, &power_up_vertices);
+            shown_power_ups = power_up_vertices;
+        }
+        let charging = game.serve_charge > 0.;
+        if charging {
+            #[allow(clippy::cast_possible_truncation)]
+            let charge_vertices = charge_bar_geometry(game.serve_charge_fraction() as f32);
+            uploaded += charge_bar.update(&state.queue, &charge_vertices);
+        }
+        // Trails only change while the balls move, not paused, in the menu or after the match
+        shown_trails.resize(trail_drawables.len(), None);
+        for ((drawable, trail), shown) in trail_drawables.iter().zip(&trails).zip(&mut shown_trails)
+        {
+            let vertices = trail_geometry(trail, options.ball_color, options.ball_size);
+            if shown.as_ref() != Some(&vertices) {
+                uploaded += drawable.update(&state.queue, &vertices);
+                *shown = Some(vertices);
+            }
+        }
+        let show_outlines = show_boxes && phase != GamePhase::GameOver;
+        if show_outlines {
+            let boxes = game.collision_boxes();
+            // Balls split off by power-ups picking up more power-ups can outgrow the buffer
+            if boxes.len() > outline_capacity {
+                outline_capacity = boxes.len();
+                outlines = outline_drawable(&state, outline_capacity);
+            }
+            let wireframe = state.wireframe_pipeline.is_some();
+            uploaded += outlines.update(
+                &state.queue,
+                &outline_geometry(&boxes, outline_capacity, wireframe),
+            );
+        }
+        fps_uploaded += uploaded;
 
         // Rendering
-        let output = state
-            .surface
-            .get_current_texture()
-            .expect("Failed to get texture");
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-
-        let mut encoder = state
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
+        let mut drawables = vec![];
+        match phase {
+            GamePhase::Menu => {
+                drawables.push(&net);
+                drawables.extend(&walls);
+            }
+            GamePhase::Demo | GamePhase::Playing => {
+                drawables.push(&paddle_1);
+                if !game.practice_wall {
+                    drawables.push(&paddle_2);
+                }
+                drawables.extend(&practice_wall);
+                drawables.extend(&balls);
+                drawables.extend(&trail_drawables);
+                drawables.push(&power_ups);
+                drawables.push(&net);
+                drawables.extend(&walls);
+                if charging {
+                    drawables.push(&charge_bar);
+                }
+            }
+            // Trails follow the live ball, which the replay isn't showing
+            GamePhase::Replay => {
+                drawables.push(&paddle_1);
+                if !game.practice_wall {
+                    drawables.push(&paddle_2);
+                }
+                drawables.extend(&practice_wall);
+                drawables.extend(&balls);
+                drawables.push(&power_ups);
+                drawables.push(&net);
+                drawables.extend(&walls);
+            }
+            GamePhase::GameOver => {}
+        }
+        let countdown = format!("{:.0}", game.serve_timer.ceil());
+        let menu_text = options_menu.as_ref().map(PauseMenu::text);
+        let message = match phase {
+            GamePhase::Playing if paused => {
+                Some(menu_text.as_deref().unwrap_or("Paused\nO for Options"))
+            }
+            GamePhase::Demo | GamePhase::Menu if client.is_some() => Some("Waiting for the Host"),
+            GamePhase::Demo => Some("Press Any Key"),
+            GamePhase::Menu => Some("Press Space to Start"),
+            GamePhase::Playing if game.serve_timer > 0. => Some(countdown.as_str()),
+            GamePhase::Replay => Some("Replay"),
+            // Only offered where R restarts the match
+            GamePhase::GameOver if client.is_some() || playback.is_some() => None,
+            GamePhase::GameOver if game.coop => Some("Game Over\nR to Restart"),
+            GamePhase::GameOver => Some("R to Restart"),
+            GamePhase::Playing => None,
+        };
+        let status = (speed_steps != 0).then(|| format!("Speed x{}", time_scale(speed_steps)));
+        let debug = show_debug.then(|| debug_text(&game, frame_time));
+        state.draw_text(Some(&game), message, status.as_deref(), debug.as_deref());
+        let screenshot = std::mem::take(&mut input.lock().unwrap().screenshot_requested);
+        let render_start = Instant::now();
+        let result = if state.is_minimized() {
+            Ok(())
+        } else {
+            state.render(&drawables, show_outlines.then_some(&outlines), screenshot)
+        };
+        match result {
+            Ok(()) => {}
+            // Some drivers drop the surface on minimize and restore, set it up again and skip
+            // this frame
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => state.reconfigure(),
+            Err(wgpu::SurfaceError::Timeout) => debug!("Timed out getting a frame, trying again"),
+            Err(wgpu::SurfaceError::OutOfMemory) => state.window.set_should_close(true),
+            Err(error) => error!("Failed to render frame: {error}"),
+        }
+        if let Some(profile) = &mut profile {
+            profile.record(dt, render_start.elapsed().as_secs_f64());
+        }
 
-        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Render Pass"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(Color::BLACK),
-                    store: wgpu::StoreOp::Store,
-                },
-            })],
-            depth_stencil_attachment: None,
-            timestamp_writes: None,
-            occlusion_query_set: None,
-        });
+        if options.low_latency {
+            // Input is read right before stepping and drawing and nothing is queued behind vsync,
+            // so the time to present plus the display's own scan-out is about all the lag there is
+            latency_total += glfw.get_time() - polled;
+            latency_frames += 1;
+            if latency_frames == LATENCY_LOG_FRAMES {
+                info!(
+                    "Average input-to-photon estimate: {:.2} ms",
+                    latency_total / f64::from(latency_frames) * 1_000.
+                );
+                (latency_total, latency_frames) = (0., 0);
+            }
+        }
 
-        render_pass.set_pipeline(&state.render_pipeline);
-        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-        render_pass.draw_indexed(0..combined_indices.len() as u32, 0, 0..1);
-        drop(render_pass);
-        state.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        if let Some(fps_cap) = options.fps_cap {
+            let frame_time = glfw.get_time() - now;
+            let target = 1. / f64::from(fps_cap.max(1));
+            if state.config.present_mode == wgpu::PresentMode::Immediate && frame_time < target {
+                std::thread::sleep(std::time::Duration::from_secs_f64(target - frame_time));
+            }
+        }
     }
+
+    state.finish_gpu_work();
+    save_recording(&options, recording.as_ref());
+    save_profile(&options, profile.as_ref());
+
+    Ok(())
 }
 
 fn main() {
-    pollster::block_on(run());
+    // Warnings show by default, `RUST_LOG=debug` reveals every serve and point as well
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{error}");
+            std::process::exit(2);
+        }
+    };
+    if options.list_adapters {
+        list_adapters();
+        return;
+    }
+
+    let playback = options.replay.as_ref().map(|path| {
+        Replay::load(path).unwrap_or_else(|error| {
+            eprintln!("Failed to load replay from {}: {error}", path.display());
+            std::process::exit(2);
+        })
+    });
+    let seed = match (&playback, options.seed) {
+        (Some(replay), _) => replay.seed,
+        (None, Some(seed)) => seed,
+        (None, None) => {
+            let seed = rand::random();
+            println!("Seed: {seed}");
+            seed
+        }
+    };
+
+    if let Some(seconds) = options.headless {
+        run_headless(&options, seconds, seed, playback);
+        return;
+    }
+
+    if let Err(error) = run(options, seed, playback) {
+        eprintln!("{error}");
+        std::process::exit(1);
+    }
 }
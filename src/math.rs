@@ -0,0 +1,111 @@
+use std::ops::{Add, Mul, Sub};
+
+use radians::Wrap64;
+
+/// A point or displacement in screen units.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vec2 {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Vec2 {
+    pub const fn new(x: f64, y: f64) -> Self {
+        Vec2 { x, y }
+    }
+
+    /// The unit vector pointing along `direction`.
+    pub fn from_direction(direction: Wrap64) -> Self {
+        let (sin, cos) = direction.sin_cos();
+        Vec2::new(cos, sin)
+    }
+
+    /// The angle of the vector from the positive x axis, zero for the zero vector.
+    pub fn direction(self) -> Wrap64 {
+        Wrap64::atan2(self.y, self.x)
+    }
+
+    pub fn dot(self, other: Vec2) -> f64 {
+        self.x * other.x + self.y * other.y
+    }
+
+    pub fn length(self) -> f64 {
+        self.x.hypot(self.y)
+    }
+
+    /// The unit vector pointing the same way, `None` for a vector without a finite, non-zero
+    /// length, which has no direction to keep.
+    pub fn normalize(self) -> Option<Self> {
+        let length = self.length();
+        (length > 0. && length.is_finite()).then(|| self * length.recip())
+    }
+
+    pub fn midpoint(self, other: Vec2) -> Self {
+        Vec2::new(
+            f64::midpoint(self.x, other.x),
+            f64::midpoint(self.y, other.y),
+        )
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Vec2;
+
+    fn add(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Vec2;
+
+    fn sub(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Mul<f64> for Vec2 {
+    type Output = Vec2;
+
+    fn mul(self, scale: f64) -> Vec2 {
+        Vec2::new(self.x * scale, self.y * scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 1e-9;
+
+    #[test]
+    fn direction_round_trips() {
+        for val in [0., 0.5, 1.5, 3., -0.4, -2.8] {
+            let direction = Vec2::from_direction(Wrap64::wrap(val)).direction();
+            assert!((direction.val() - val).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn normalize_keeps_the_direction_at_unit_length() {
+        let a = Vec2::new(3., -4.);
+        let unit = a.normalize().unwrap();
+        assert!((unit.length() - 1.).abs() < EPSILON);
+        assert!((unit.direction().val() - a.direction().val()).abs() < EPSILON);
+        assert_eq!(Vec2::default().normalize(), None);
+    }
+
+    #[test]
+    fn normalize_rejects_vectors_without_a_finite_length() {
+        assert_eq!(Vec2::new(f64::NAN, 1.).normalize(), None);
+        assert_eq!(Vec2::new(f64::MAX, f64::MAX).normalize(), None);
+        assert_eq!(Vec2::new(f64::NEG_INFINITY, 0.).normalize(), None);
+    }
+
+    #[test]
+    fn perpendicular_vectors_have_zero_dot_product() {
+        let a = Vec2::new(3., -4.);
+        assert!((a.length() - 5.).abs() < EPSILON);
+        assert!(a.dot(Vec2::new(4., 3.)).abs() < EPSILON);
+    }
+}
@@ -0,0 +1,140 @@
+use std::{
+    io::{self, ErrorKind},
+    net::{SocketAddr, UdpSocket},
+};
+
+use log::{debug, info};
+use pong::physics::{GameState, InputState};
+
+use crate::GamePhase;
+
+/// The largest payload a UDP datagram can carry, enough for any snapshot.
+const MAX_PACKET_SIZE: usize = 65_507;
+
+/// Runs the match for a remote client: reads its keys and streams back the state every frame.
+pub struct Host {
+    socket: UdpSocket,
+    /// Where snapshots go, learned from the first input the client sends. Nobody else can take
+    /// over player 2 after that.
+    client: Option<SocketAddr>,
+    sequence: u64,
+    buffer: Vec<u8>,
+}
+
+impl Host {
+    /// Listens for a client on `port` on every interface.
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", port))?;
+        socket.set_nonblocking(true)?;
+        info!("Hosting on port {port}");
+        Ok(Host {
+            socket,
+            client: None,
+            sequence: 0,
+            buffer: vec![0; MAX_PACKET_SIZE],
+        })
+    }
+
+    /// The newest input the client sent since the last call, if any. Packets from anyone but
+    /// the first client to send valid input are ignored.
+    pub fn receive_input(&mut self) -> Option<InputState> {
+        let mut newest = None;
+        while let Some((length, from)) = receive(&self.socket, &mut self.buffer) {
+            if self.client.is_some_and(|client| client != from) {
+                debug!("Ignoring a packet from {from}, another client is already playing");
+                continue;
+            }
+            let Ok(input) = serde_json::from_slice(&self.buffer[..length]) else {
+                debug!("Ignoring a malformed packet from {from}");
+                continue;
+            };
+            if self.client.is_none() {
+                info!("Client connected from {from}");
+                self.client = Some(from);
+            }
+            newest = Some(input);
+        }
+        newest
+    }
+
+    /// Sends the whole match and its `phase` to the client, once one has been heard from.
+    pub fn send_state(&mut self, game: &GameState, phase: GamePhase) {
+        let Some(client) = self.client else {
+            return;
+        };
+        self.sequence += 1;
+        match serde_json::to_vec(&(self.sequence, phase, game)) {
+            Ok(packet) => {
+                if let Err(error) = self.socket.send_to(&packet, client) {
+                    debug!("Failed to send a snapshot to {client}: {error}");
+                }
+            }
+            Err(error) => debug!("Failed to encode a snapshot: {error}"),
+        }
+    }
+}
+
+/// Plays against a [`Host`]: sends the local keys and shows whatever state comes back.
+pub struct Client {
+    socket: UdpSocket,
+    /// Sequence number of the newest snapshot shown, so late ones can be dropped.
+    sequence: u64,
+    buffer: Vec<u8>,
+}
+
+impl Client {
+    /// Opens a socket talking only to the host at `address`, like `192.168.1.20:7777`.
+    pub fn connect(address: &str) -> io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+        socket.connect(address)?;
+        socket.set_nonblocking(true)?;
+        info!("Connecting to {address}");
+        Ok(Client {
+            socket,
+            sequence: 0,
+            buffer: vec![0; MAX_PACKET_SIZE],
+        })
+    }
+
+    pub fn send_input(&self, input: &InputState) {
+        match serde_json::to_vec(input) {
+            Ok(packet) => {
+                // Until the host is up every send fails, and that's fine
+                if let Err(error) = self.socket.send(&packet) {
+                    debug!("Failed to send input: {error}");
+                }
+            }
+            Err(error) => debug!("Failed to encode input: {error}"),
+        }
+    }
+
+    /// The newest snapshot the host sent since the last call, with the host's phase, if any.
+    pub fn receive_state(&mut self) -> Option<(GamePhase, GameState)> {
+        let mut newest = None;
+        while let Some((length, _)) = receive(&self.socket, &mut self.buffer) {
+            match serde_json::from_slice::<(u64, GamePhase, GameState)>(&self.buffer[..length]) {
+                // UDP can reorder packets, and an older snapshot would jump back in time
+                Ok((sequence, phase, game)) if sequence > self.sequence => {
+                    self.sequence = sequence;
+                    newest = Some((phase, game));
+                }
+                Ok(_) => {}
+                Err(error) => debug!("Ignoring a malformed snapshot: {error}"),
+            }
+        }
+        newest
+    }
+}
+
+/// Reads one waiting datagram into `buffer`, or `None` once there are none left.
+fn receive(socket: &UdpSocket, buffer: &mut [u8]) -> Option<(usize, SocketAddr)> {
+    match socket.recv_from(buffer) {
+        Ok(received) => Some(received),
+        Err(error) if error.kind() == ErrorKind::WouldBlock => None,
+        // Errors like a refused connection belong to an earlier send, try again next frame
+        Err(error) => {
+            debug!("Failed to receive a packet: {error}");
+            None
+        }
+    }
+}
@@ -0,0 +1,583 @@
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use pong::physics::{AiConfig, BallShape, Side, GAME_TARGET_SCORE, SERVE_DELAY};
+use wgpu::PresentMode;
+
+use crate::{
+    keys::{parse_key, KeyBindings},
+    settings::{Settings, DEFAULT_SETTINGS_PATH},
+    theme::{parse_theme, Theme},
+    BALL_HALF_SIZE, PADDLE_HALF_HEIGHT,
+};
+
+const DEFAULT_WINDOW_SIZE: (u32, u32) = (1_000, 600);
+
+/// Flags that stand alone. Every other flag takes the argument after it as its value.
+const SWITCHES: [&str; 8] = [
+    "--ai",
+    "--p2-invert",
+    "--serve-accel",
+    "--show-fps",
+    "--list-adapters",
+    "--wall",
+    "--coop",
+    "--demo",
+];
+
+/// Flags that run without a window, which have no business leaving a settings file behind.
+const NON_INTERACTIVE: [&str; 3] = ["--list-adapters", "--headless", "--replay"];
+
+/// More balls than this no longer fit side by side at the serve.
+const MAX_BALLS: usize = 10;
+
+/// Largest `--ball-size`, a ball a tenth as tall as the field.
+const MAX_BALL_SIZE: f32 = 0.1;
+
+/// Settings picked on the command line, on top of those from the settings file.
+#[allow(clippy::struct_excessive_bools)]
+pub struct Options {
+    pub ai: bool,
+    /// How well the computer plays when `ai` is set.
+    pub difficulty: AiConfig,
+    pub p1_color: [f32; 3],
+    pub p2_color: [f32; 3],
+    pub ball_color: [f32; 3],
+    /// Color the screen is cleared to behind the field.
+    pub background: [f32; 3],
+    /// Points needed to win.
+    pub target_score: u32,
+    /// Games in the series, the first to win a majority of them taking the match.
+    pub best_of: u32,
+    pub paddle_half_height: f32,
+    /// Height player 1's paddle starts centred at, kept clear of the top and bottom.
+    pub p1_start: f32,
+    /// Height player 2's paddle starts centred at, kept clear of the top and bottom.
+    pub p2_start: f32,
+    /// Multiplier on the ball's speed, only changed from the options menu.
+    pub ball_speed: f64,
+    pub key_bindings: KeyBindings,
+    /// Whether player 2's up and down are swapped, whatever keys they're bound to.
+    pub p2_invert: bool,
+    /// Fixed steps every input is held back before it reaches the game, to try out latency.
+    pub input_delay: usize,
+    /// Number of balls in play at once.
+    pub balls: usize,
+    /// Half the ball's width and height.
+    pub ball_size: f32,
+    /// Whether the ball is drawn and hit as a square or as the circle inside it.
+    pub ball_shape: BallShape,
+    /// The side every serve heads towards, `None` tossing a coin for each.
+    pub serve_side: Option<Side>,
+    /// Countdown before every serve, in seconds.
+    pub serve_delay: f64,
+    /// Whether serves start slow and speed up to full speed over their first second.
+    pub serve_accel: bool,
+    /// Seconds each game lasts before whoever is ahead wins, `None` for no limit.
+    pub time_limit: Option<f64>,
+    /// Seconds between power-up spawns, `None` playing without them.
+    pub power_ups: Option<f64>,
+    /// Width over height of the field, letterboxed into the window with visible walls. `None`
+    /// keeps the square field with invisible walls.
+    pub aspect: Option<f64>,
+    /// Initial window width and height, in screen coordinates.
+    pub window_size: (u32, u32),
+    /// Requested presentation mode, `None` keeping the surface's preferred one.
+    pub present_mode: Option<PresentMode>,
+    /// Set by `--vsync off`: frames are presented without waiting for vsync and show the newest
+    /// physics step as is, rather than blending towards it one step behind. Cleared when the
+    /// surface can't present that way.
+    pub low_latency: bool,
+    /// Multisample anti-aliasing samples per pixel, lowered to what the GPU supports.
+    pub msaa: u32,
+    /// Frame-rate limit, only enforced with [`PresentMode::Immediate`].
+    pub fps_cap: Option<u32>,
+    /// Whether the window title shows the frame rate.
+    pub show_fps: bool,
+    /// Whether to print the GPUs wgpu can see and exit instead of playing.
+    pub list_adapters: bool,
+    /// Whether player 1 practises alone, rallying against a wall in place of player 2.
+    pub wall: bool,
+    /// Whether both players keep one ball in play together for as long as they can.
+    pub coop: bool,
+    /// Whether the computer plays both paddles in an endless attract-mode loop until a key is
+    /// pressed.
+    pub demo: bool,
+    /// Simulated seconds to play without opening a window, for scripted runs.
+    pub headless: Option<u32>,
+    /// Seeds every random choice in the match, so the same seed and inputs replay it exactly.
+    pub seed: Option<u64>,
+    /// Where to save the match's inputs for replaying it later.
+    pub record: Option<PathBuf>,
+    /// A recording to play back instead of reading the players' input.
+    pub replay: Option<PathBuf>,
+    /// Where to write a CSV of every frame's timings when the window closes.
+    pub profile: Option<PathBuf>,
+    /// Port to host a network match on, the remote player taking player 2.
+    pub host: Option<u16>,
+    /// `ADDRESS:PORT` of a host to join as player 2.
+    pub connect: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            ai: false,
+            difficulty: AiConfig::MEDIUM,
+            p1_color: [1., 1., 1.],
+            p2_color: [1., 1., 1.],
+            ball_color: [1., 1., 1.],
+            background: [0., 0., 0.],
+            target_score: GAME_TARGET_SCORE,
+            best_of: 1,
+            ball_speed: 1.,
+            paddle_half_height: PADDLE_HALF_HEIGHT,
+            p1_start: 0.,
+            p2_start: 0.,
+            key_bindings: KeyBindings::default(),
+            p2_invert: false,
+            input_delay: 0,
+            balls: 1,
+            ball_shape: BallShape::Square,
+            ball_size: BALL_HALF_SIZE,
+            time_limit: None,
+            serve_side: None,
+            serve_delay: SERVE_DELAY,
+            serve_accel: false,
+            power_ups: None,
+            aspect: None,
+            window_size: DEFAULT_WINDOW_SIZE,
+            present_mode: None,
+            low_latency: false,
+            msaa: 4,
+            fps_cap: None,
+            show_fps: false,
+            list_adapters: false,
+            wall: false,
+            coop: false,
+            demo: false,
+            headless: None,
+            seed: None,
+            record: None,
+            replay: None,
+            profile: None,
+            host: None,
+            connect: None,
+        }
+    }
+}
+
+impl Options {
+    /// Parses the arguments following the program name, starting from the settings file named
+    /// by `--config` or [`DEFAULT_SETTINGS_PATH`]. Only a game in a window writes the default
+    /// settings file when there isn't one.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let flags = pair_flags(args);
+        let config_path = flags
+            .iter()
+            .rfind(|(flag, _)| flag == "--config")
+            .and_then(|(_, value)| value.as_deref())
+            .unwrap_or(DEFAULT_SETTINGS_PATH);
+        let interactive = !flags
+            .iter()
+            .any(|(flag, _)| NON_INTERACTIVE.contains(&flag.as_str()));
+        let settings = Settings::load(Path::new(config_path), interactive)?;
+
+        let mut options = Options::from_settings(&settings)?;
+
+        for (arg, mut value) in flags {
+            let mut value = || value.take().ok_or(format!("Missing value for `{arg}`"));
+            match arg.as_str() {
+                "--config" => {
+                    value()?;
+                }
+                "--ai" => options.ai = true,
+                "--difficulty" => options.difficulty = parse_difficulty(&value()?)?,
+                "--p1-color" => options.p1_color = parse_color(&value()?)?,
+                "--p2-color" => options.p2_color = parse_color(&value()?)?,
+                "--ball-color" => options.ball_color = parse_color(&value()?)?,
+                "--bg-color" => options.background = parse_color(&value()?)?,
+                // Color flags after `--theme` still override it
+                "--theme" => options.apply_theme(parse_theme(&value()?)?),
+                "--target-score" => options.target_score = parse_target_score(&value()?)?,
+                "--best-of" => options.best_of = parse_best_of(&value()?)?,
+                "--paddle-half-height" => {
+                    options.paddle_half_height = parse_paddle_half_height(&value()?)?;
+                }
+                "--p1-start" => options.p1_start = parse_number(&arg, &value()?)?,
+                "--p2-start" => options.p2_start = parse_number(&arg, &value()?)?,
+                "--p2-invert" => options.p2_invert = true,
+                "--input-delay" => options.input_delay = parse_number(&arg, &value()?)?,
+                "--balls" => options.balls = parse_ball_count(&value()?)?,
+                "--ball-size" => options.ball_size = parse_ball_size(&value()?)?,
+                "--ball-shape" => options.ball_shape = parse_ball_shape(&value()?)?,
+                "--time-limit" => options.time_limit = Some(parse_time_limit(&value()?)?),
+                "--serve" => options.serve_side = parse_serve_side(&value()?)?,
+                "--serve-delay" => options.serve_delay = parse_serve_delay(&value()?)?,
+                "--serve-accel" => options.serve_accel = true,
+                "--power-ups" => options.power_ups = Some(parse_power_up_interval(&value()?)?),
+                "--aspect" => options.aspect = Some(parse_aspect(&value()?)?),
+                "--window-size" => {
+                    let value = value()?;
+                    match parse_window_size(&value) {
+                        Some(size) => options.window_size = size,
+                        None => log::warn!(
+                            "Ignoring window size `{value}`, expected something like `1280x720`"
+                        ),
+                    }
+                }
+                "--present-mode" => options.present_mode = Some(parse_present_mode(&value()?)?),
+                "--vsync" => {
+                    options.low_latency = !parse_vsync(&value()?)?;
+                    options.present_mode = Some(if options.low_latency {
+                        PresentMode::Immediate
+                    } else {
+                        PresentMode::Fifo
+                    });
+                }
+                "--msaa" => options.msaa = parse_msaa(&value()?)?,
+                "--fps-cap" => options.fps_cap = Some(parse_number(&arg, &value()?)?),
+                "--show-fps" => options.show_fps = true,
+                "--list-adapters" => options.list_adapters = true,
+                "--wall" => options.wall = true,
+                "--coop" => options.coop = true,
+                "--demo" => options.demo = true,
+                "--headless" => options.headless = Some(parse_number(&arg, &value()?)?),
+                "--seed" => options.seed = Some(parse_number(&arg, &value()?)?),
+                "--record" => options.record = Some(PathBuf::from(value()?)),
+                "--replay" => options.replay = Some(PathBuf::from(value()?)),
+                "--profile" => options.profile = Some(PathBuf::from(value()?)),
+                "--host" => options.host = Some(parse_number(&arg, &value()?)?),
+                "--connect" => options.connect = Some(value()?),
+                _ => return Err(format!("Unknown argument `{arg}`")),
+            }
+        }
+
+        // Clamped once the paddle size is known, whichever order the flags came in
+        options.p1_start =
+            clamp_paddle_start("--p1-start", options.p1_start, options.paddle_half_height);
+        options.p2_start =
+            clamp_paddle_start("--p2-start", options.p2_start, options.paddle_half_height);
+
+        if options.host.is_some() && options.connect.is_some() {
+            return Err("`--host` and `--connect` can't be used together".to_string());
+        }
+        // The remote player takes player 2, so there's nobody left for the computer to play
+        if options.ai && (options.host.is_some() || options.connect.is_some()) {
+            return Err("`--ai` can't be used with `--host` or `--connect`".to_string());
+        }
+        if options.wall && (options.ai || options.host.is_some() || options.connect.is_some()) {
+            return Err(
+                "`--wall` replaces player 2, so it can't be used with `--ai`, `--host` or `--connect`"
+                    .to_string(),
+            );
+        }
+
+        // A co-op match is one rally, there's no leader to time out or games to count
+        if options.coop && (options.wall || options.best_of > 1 || options.time_limit.is_some()) {
+            return Err(
+                "`--coop` can't be used with `--wall`, `--best-of` or `--time-limit`".to_string(),
+            );
+        }
+
+        Ok(options)
+    }
+
+    fn apply_theme(&mut self, theme: &Theme) {
+        self.p1_color = theme.p1_color;
+        self.p2_color = theme.p2_color;
+        self.ball_color = theme.ball_color;
+        self.background = theme.background;
+    }
+
+    fn from_settings(settings: &Settings) -> Result<Self, String> {
+        let present_mode = settings
+            .present_mode
+            .as_deref()
+            .map(parse_present_mode)
+            .transpose()?;
+        if settings.target_score == 0 {
+            return Err("`target_score` must be at least 1".to_string());
+        }
+
+        let key_bindings = KeyBindings {
+            p1_up: parse_key(&settings.keys.p1_up)?,
+            p1_down: parse_key(&settings.keys.p1_down)?,
+            p2_up: parse_key(&settings.keys.p2_up)?,
+            p2_down: parse_key(&settings.keys.p2_down)?,
+        };
+        key_bindings
+            .validate()
+            .map_err(|error| format!("Invalid key bindings: {error}"))?;
+
+        Ok(Options {
+            difficulty: parse_difficulty(&settings.difficulty)?,
+            p1_color: check_color(settings.p1_color)?,
+            p2_color: check_color(settings.p2_color)?,
+            ball_color: check_color(settings.ball_color)?,
+            target_score: settings.target_score,
+            key_bindings,
+            present_mode,
+            ..Options::default()
+        })
+    }
+}
+
+/// Pairs every flag with the argument after it, unless it's one of the [`SWITCHES`]. A flag
+/// missing its value at the end is left without one, for parsing it to report.
+fn pair_flags(args: impl IntoIterator<Item = String>) -> Vec<(String, Option<String>)> {
+    let mut args = args.into_iter();
+    let mut flags = Vec::new();
+    while let Some(flag) = args.next() {
+        let value = if SWITCHES.contains(&flag.as_str()) {
+            None
+        } else {
+            args.next()
+        };
+        flags.push((flag, value));
+    }
+    flags
+}
+
+/// Rejects a color from the settings file with a component outside `0.0..=1.0`.
+fn check_color(color: [f32; 3]) -> Result<[f32; 3], String> {
+    if color
+        .iter()
+        .all(|component| (0.0..=1.0).contains(component))
+    {
+        Ok(color)
+    } else {
+        Err(format!("Color {color:?} has a component outside 0.0..=1.0"))
+    }
+}
+
+/// Parses an `r,g,b` triple with every component in `0.0..=1.0`.
+fn parse_color(value: &str) -> Result<[f32; 3], String> {
+    let components = value
+        .split(',')
+        .map(|component| {
+            let component: f32 = component
+                .trim()
+                .parse()
+                .map_err(|_| format!("`{component}` in color `{value}` is not a number"))?;
+            if (0.0..=1.0).contains(&component) {
+                Ok(component)
+            } else {
+                Err(format!(
+                    "`{component}` in color `{value}` is outside 0.0..=1.0"
+                ))
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    components
+        .try_into()
+        .map_err(|_| format!("Color `{value}` must have exactly three components, like `1,0.5,0`"))
+}
+
+/// Parses a ball half-size, which must be big enough to see and small enough to play with.
+fn parse_ball_size(value: &str) -> Result<f32, String> {
+    let size: f32 = parse_number("--ball-size", value)?;
+    if size > 0. && size <= MAX_BALL_SIZE {
+        Ok(size)
+    } else {
+        Err(format!(
+            "Ball size `{value}` must be greater than 0 and at most {MAX_BALL_SIZE}"
+        ))
+    }
+}
+
+/// Parses a paddle half-height, which must leave the paddle fitting on screen.
+fn parse_paddle_half_height(value: &str) -> Result<f32, String> {
+    let half_height: f32 = parse_number("--paddle-half-height", value)?;
+    if half_height > 0. && half_height <= 1. {
+        Ok(half_height)
+    } else {
+        Err(format!(
+            "Paddle half-height `{value}` must be greater than 0 and at most 1"
+        ))
+    }
+}
+
+/// Keeps a paddle centred at `start` from sticking out past the top or bottom of the field.
+fn clamp_paddle_start(arg: &str, start: f32, half_height: f32) -> f32 {
+    let limit = 1. - half_height;
+    if (-limit..=limit).contains(&start) {
+        return start;
+    }
+    // NaN isn't in any range, so it lands in the middle
+    let clamped = if start.is_nan() {
+        0.
+    } else {
+        start.clamp(-limit, limit)
+    };
+    log::warn!("Clamping `{arg}` from {start} to {clamped} to keep the paddle on screen");
+    clamped
+}
+
+fn parse_difficulty(value: &str) -> Result<AiConfig, String> {
+    match value {
+        "easy" => Ok(AiConfig::EASY),
+        "medium" => Ok(AiConfig::MEDIUM),
+        "hard" => Ok(AiConfig::HARD),
+        _ => Err(format!(
+            "Unknown difficulty `{value}`, expected `easy`, `medium` or `hard`"
+        )),
+    }
+}
+
+fn parse_ball_shape(value: &str) -> Result<BallShape, String> {
+    match value {
+        "square" => Ok(BallShape::Square),
+        "circle" => Ok(BallShape::Circle),
+        _ => Err(format!(
+            "Unknown ball shape `{value}`, expected `square` or `circle`"
+        )),
+    }
+}
+
+fn parse_serve_side(value: &str) -> Result<Option<Side>, String> {
+    match value {
+        "left" => Ok(Some(Side::Left)),
+        "right" => Ok(Some(Side::Right)),
+        "random" => Ok(None),
+        _ => Err(format!(
+            "Unknown serve side `{value}`, expected `left`, `right` or `random`"
+        )),
+    }
+}
+
+fn parse_target_score(value: &str) -> Result<u32, String> {
+    match parse_number("--target-score", value)? {
+        0 => Err("Target score must be at least 1".to_string()),
+        score => Ok(score),
+    }
+}
+
+fn parse_best_of(value: &str) -> Result<u32, String> {
+    match parse_number("--best-of", value)? {
+        0 => Err("A series must be at least 1 game".to_string()),
+        games => Ok(games),
+    }
+}
+
+fn parse_ball_count(value: &str) -> Result<usize, String> {
+    let count = parse_number("--balls", value)?;
+    if (1..=MAX_BALLS).contains(&count) {
+        Ok(count)
+    } else {
+        Err(format!(
+            "Ball count `{value}` must be between 1 and {MAX_BALLS}"
+        ))
+    }
+}
+
+fn parse_serve_delay(value: &str) -> Result<f64, String> {
+    let delay: f64 = parse_number("--serve-delay", value)?;
+    if delay.is_finite() && delay >= 0. {
+        Ok(delay)
+    } else {
+        Err(format!(
+            "Serve delay `{value}` must be a number of seconds, 0 or more"
+        ))
+    }
+}
+
+fn parse_time_limit(value: &str) -> Result<f64, String> {
+    let limit: f64 = parse_number("--time-limit", value)?;
+    if limit.is_finite() && limit > 0. {
+        Ok(limit)
+    } else {
+        Err(format!(
+            "Time limit `{value}` must be a number of seconds greater than 0"
+        ))
+    }
+}
+
+fn parse_power_up_interval(value: &str) -> Result<f64, String> {
+    let interval: f64 = parse_number("--power-ups", value)?;
+    if interval.is_finite() && interval > 0. {
+        Ok(interval)
+    } else {
+        Err(format!(
+            "Power-up interval `{value}` must be a number of seconds above 0"
+        ))
+    }
+}
+
+/// Parses a `WIDTH:HEIGHT` ratio like `4:3`, at least as wide as it is tall.
+fn parse_aspect(value: &str) -> Result<f64, String> {
+    let error = || format!("Aspect ratio `{value}` must look like `4:3`, from `1:1` to `4:1`");
+    let (width, height) = value.split_once(':').ok_or_else(error)?;
+    let width: f64 = width.trim().parse().map_err(|_| error())?;
+    let height: f64 = height.trim().parse().map_err(|_| error())?;
+    let aspect = width / height;
+    if (1. ..=4.).contains(&aspect) {
+        Ok(aspect)
+    } else {
+        Err(error())
+    }
+}
+
+/// Parses a `WIDTHxHEIGHT` pair, both positive.
+fn parse_window_size(value: &str) -> Option<(u32, u32)> {
+    let (width, height) = value.split_once('x')?;
+    let width = width.trim().parse().ok().filter(|&width| width > 0)?;
+    let height = height.trim().parse().ok().filter(|&height| height > 0)?;
+    Some((width, height))
+}
+
+fn parse_present_mode(value: &str) -> Result<PresentMode, String> {
+    match value {
+        "fifo" => Ok(PresentMode::Fifo),
+        "mailbox" => Ok(PresentMode::Mailbox),
+        "immediate" => Ok(PresentMode::Immediate),
+        _ => Err(format!(
+            "Unknown present mode `{value}`, expected `fifo`, `mailbox` or `immediate`"
+        )),
+    }
+}
+
+fn parse_vsync(value: &str) -> Result<bool, String> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(format!(
+            "Unknown vsync setting `{value}`, expected `on` or `off`"
+        )),
+    }
+}
+
+fn parse_msaa(value: &str) -> Result<u32, String> {
+    match parse_number("--msaa", value)? {
+        count @ (1 | 2 | 4 | 8) => Ok(count),
+        _ => Err(format!("MSAA sample count `{value}` must be 1, 2, 4 or 8")),
+    }
+}
+
+fn parse_number<T: FromStr>(arg: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("`{value}` is not a valid value for `{arg}`"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flag_values_are_never_read_as_flags() {
+        let args = ["--record", "--config", "--ai", "--config", "mine.toml"].map(String::from);
+        let flags = pair_flags(args);
+        assert_eq!(
+            flags,
+            [
+                ("--record".to_string(), Some("--config".to_string())),
+                ("--ai".to_string(), None),
+                ("--config".to_string(), Some("mine.toml".to_string())),
+            ]
+        );
+    }
+}
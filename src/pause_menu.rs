@@ -0,0 +1,188 @@
+use crate::physics::AiConfig;
+
+/// Difficulties the menu steps through, easiest first.
+const DIFFICULTIES: [(&str, AiConfig); 3] = [
+    ("Easy", AiConfig::EASY),
+    ("Medium", AiConfig::MEDIUM),
+    ("Hard", AiConfig::HARD),
+];
+const MIN_BALL_SPEED: f64 = 0.5;
+const MAX_BALL_SPEED: f64 = 2.;
+const BALL_SPEED_STEP: f64 = 0.25;
+const MIN_PADDLE_HALF_HEIGHT: f32 = 0.05;
+const MAX_PADDLE_HALF_HEIGHT: f32 = 0.5;
+const PADDLE_HALF_HEIGHT_STEP: f32 = 0.05;
+/// Ball speed, paddle size and difficulty.
+const ROWS: usize = 3;
+
+/// A key press the options menu responds to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuKey {
+    Up,
+    Down,
+    Left,
+    Right,
+    Confirm,
+    Back,
+}
+
+/// What the options menu can change in the middle of a match.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LiveSettings {
+    /// Multiplier on every ball's speed.
+    pub ball_speed: f64,
+    pub paddle_half_height: f32,
+    pub difficulty: AiConfig,
+}
+
+/// What became of the menu after a key press.
+#[derive(Debug, PartialEq)]
+pub enum MenuOutcome {
+    /// Still open.
+    Open,
+    /// Closed with Enter, these settings taking effect right away.
+    Apply(LiveSettings),
+    /// Closed with Escape, leaving everything as it was.
+    Cancel,
+}
+
+/// The options overlay opened from the pause screen. Up and down pick a row, left and right
+/// change it, and nothing applies until Enter.
+pub struct PauseMenu {
+    selected: usize,
+    pending: LiveSettings,
+}
+
+impl PauseMenu {
+    pub fn open(current: LiveSettings) -> Self {
+        PauseMenu {
+            selected: 0,
+            pending: current,
+        }
+    }
+
+    pub fn handle(&mut self, key: MenuKey) -> MenuOutcome {
+        match key {
+            MenuKey::Up => self.selected = (self.selected + ROWS - 1) % ROWS,
+            MenuKey::Down => self.selected = (self.selected + 1) % ROWS,
+            MenuKey::Left => self.adjust(-1),
+            MenuKey::Right => self.adjust(1),
+            MenuKey::Confirm => return MenuOutcome::Apply(self.pending),
+            MenuKey::Back => return MenuOutcome::Cancel,
+        }
+        MenuOutcome::Open
+    }
+
+    /// Steps the selected row up or down by one, staying within its range.
+    fn adjust(&mut self, steps: i8) {
+        let pending = &mut self.pending;
+        match self.selected {
+            0 => {
+                let speed = pending.ball_speed + BALL_SPEED_STEP * f64::from(steps);
+                pending.ball_speed = speed.clamp(MIN_BALL_SPEED, MAX_BALL_SPEED);
+            }
+            1 => {
+                // Snapped to the step, sizes from the command line may fall between them
+                let steps_in = (pending.paddle_half_height / PADDLE_HALF_HEIGHT_STEP).round();
+                let half_height = (steps_in + f32::from(steps)) * PADDLE_HALF_HEIGHT_STEP;
+                pending.paddle_half_height =
+                    half_height.clamp(MIN_PADDLE_HALF_HEIGHT, MAX_PADDLE_HALF_HEIGHT);
+            }
+            _ => {
+                let index = difficulty_index(pending.difficulty);
+                let index = index
+                    .saturating_add_signed(steps.into())
+                    .min(DIFFICULTIES.len() - 1);
+                pending.difficulty = DIFFICULTIES[index].1;
+            }
+        }
+    }
+
+    /// The menu as lines of text, the selected row marked.
+    pub fn text(&self) -> String {
+        let LiveSettings {
+            ball_speed,
+            paddle_half_height,
+            difficulty,
+        } = self.pending;
+        let rows = [
+            format!("Ball speed  x{ball_speed:.2}"),
+            format!("Paddle size  {paddle_half_height:.2}"),
+            format!(
+                "Difficulty  {}",
+                DIFFICULTIES[difficulty_index(difficulty)].0
+            ),
+        ];
+        let mut lines = vec!["Options".to_string()];
+        for (index, row) in rows.into_iter().enumerate() {
+            let marker = if index == self.selected { ">" } else { " " };
+            lines.push(format!("{marker} {row}"));
+        }
+        lines.push("Enter to apply, Esc to go back".to_string());
+        lines.join("\n")
+    }
+}
+
+/// Where `difficulty` sits in [`DIFFICULTIES`], medium for anything else.
+fn difficulty_index(difficulty: AiConfig) -> usize {
+    DIFFICULTIES
+        .iter()
+        .position(|&(_, config)| config == difficulty)
+        .unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defaults() -> LiveSettings {
+        LiveSettings {
+            ball_speed: 1.,
+            paddle_half_height: 0.2,
+            difficulty: AiConfig::MEDIUM,
+        }
+    }
+
+    #[test]
+    fn changes_apply_only_on_confirm() {
+        let mut menu = PauseMenu::open(defaults());
+        assert_eq!(menu.handle(MenuKey::Right), MenuOutcome::Open);
+        assert_eq!(menu.handle(MenuKey::Back), MenuOutcome::Cancel);
+
+        let mut menu = PauseMenu::open(defaults());
+        menu.handle(MenuKey::Right);
+        menu.handle(MenuKey::Down);
+        menu.handle(MenuKey::Left);
+        menu.handle(MenuKey::Up);
+        menu.handle(MenuKey::Up);
+        menu.handle(MenuKey::Right);
+        let MenuOutcome::Apply(settings) = menu.handle(MenuKey::Confirm) else {
+            panic!("Enter should apply the settings");
+        };
+        assert!((settings.ball_speed - 1.25).abs() < 1e-9);
+        assert!((settings.paddle_half_height - 0.15).abs() < 1e-6);
+        assert_eq!(settings.difficulty, AiConfig::HARD);
+    }
+
+    #[test]
+    fn values_stay_within_their_ranges() {
+        let mut menu = PauseMenu::open(defaults());
+        for _ in 0..20 {
+            menu.handle(MenuKey::Right);
+        }
+        menu.handle(MenuKey::Down);
+        for _ in 0..20 {
+            menu.handle(MenuKey::Left);
+        }
+        menu.handle(MenuKey::Down);
+        for _ in 0..20 {
+            menu.handle(MenuKey::Left);
+        }
+        let MenuOutcome::Apply(settings) = menu.handle(MenuKey::Confirm) else {
+            panic!("Enter should apply the settings");
+        };
+        assert!((settings.ball_speed - MAX_BALL_SPEED).abs() < 1e-9);
+        assert!((settings.paddle_half_height - MIN_PADDLE_HALF_HEIGHT).abs() < 1e-6);
+        assert_eq!(settings.difficulty, AiConfig::EASY);
+    }
+}
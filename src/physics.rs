@@ -0,0 +1,2089 @@
+use std::{cmp::Ordering, collections::VecDeque, path::Path};
+
+use log::{debug, warn};
+use radians::Wrap64;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    math::Vec2,
+    pause_menu::MenuKey,
+    vertex::{Vertex, QUAD_UVS},
+};
+
+/// Top paddle speed, in screen units per second.
+const PADDLE_SPEED: f64 = 1.5;
+/// How quickly a held key brings the paddle up to speed, in screen units per second squared.
+const PADDLE_ACCELERATION: f64 = 12.;
+/// How quickly a released paddle slows down, in screen units per second squared.
+const PADDLE_DECELERATION: f64 = 15.;
+
+/// Steepest bounce off a paddle, in radians off horizontal, reached when the ball clips its
+/// very edge.
+const MAX_PADDLE_ANGLE: f64 = std::f64::consts::FRAC_PI_3;
+/// Steepest a ball leaves a wall, in radians off horizontal, 75 degrees. The reflection is
+/// exact up to here, and anything steeper would take ages to cross the field.
+const MAX_WALL_ANGLE: f64 = 5. * std::f64::consts::PI / 12.;
+
+// A moving paddle puts spin on the ball, curving its path by `SPIN_PER_PADDLE_SPEED` radians per
+// second for every unit of paddle speed, at most `MAX_SPIN`, fading over about `SPIN_DECAY_TIME`
+// seconds
+const SPIN_PER_PADDLE_SPEED: f64 = 1.;
+const MAX_SPIN: f64 = 1.5;
+const SPIN_DECAY_TIME: f64 = 0.6;
+
+// Ball speed, in screen units per second, at the serve and after every paddle hit
+const SERVE_SPEED: f64 = 0.6;
+/// With `--serve-accel`, serves set off this fast and reach their full speed `SERVE_RAMP_TIME`
+/// seconds later.
+const SERVE_RAMP_START_SPEED: f64 = 0.15;
+const SERVE_RAMP_TIME: f64 = 1.;
+const HIT_SPEEDUP: f64 = 1.05;
+const MAX_BALL_SPEED: f64 = 2.4;
+
+// Holding the serve key through the countdown charges the serve, reaching `CHARGED_SERVE_SPEED`
+// after `MAX_SERVE_CHARGE` seconds
+const MAX_SERVE_CHARGE: f64 = 1.;
+const CHARGED_SERVE_SPEED: f64 = 1.5;
+
+/// Slowest the ball may cross the field, in screen units per second. Wall bounces off a ball
+/// heading almost straight up or down tilt it back until it goes at least this fast sideways, so
+/// it can't bounce between the walls forever.
+const MIN_HORIZONTAL_SPEED: f64 = 0.25;
+
+/// Serves leave at most this many degrees off horizontal. It has to stay well short of 90, or a
+/// serve could bounce between the walls for ages before reaching a paddle.
+const SERVE_CONE_DEGREES: f64 = 30.;
+
+/// Default wait, in seconds, between a ball being placed for a serve and it moving.
+pub const SERVE_DELAY: f64 = 3.;
+
+/// Ball speed above which paddle hits are found by sweeping the ball's path rather than by
+/// overlap alone.
+const SWEPT_SPEED_THRESHOLD: f64 = 1.;
+
+/// Vertical gap between the starting positions of neighbouring balls.
+const BALL_SPACING: f64 = 0.15;
+
+/// Default number of points a player needs to win the game.
+pub const GAME_TARGET_SCORE: u32 = 11;
+
+/// Most power-ups waiting on the field at once.
+pub const MAX_POWER_UPS: usize = 3;
+/// Half the width and height of a power-up pickup.
+const POWER_UP_HALF_SIZE: f64 = 0.04;
+/// Seconds a power-up's effect lasts once picked up.
+const POWER_UP_DURATION: f64 = 8.;
+// How much the power-ups stretch a paddle and speed up or slow down the balls
+const BIG_PADDLE_SCALE: f64 = 1.5;
+const FAST_BALL_SCALE: f64 = 1.5;
+const SLOW_BALL_SCALE: f64 = 0.6;
+/// How much faster the balls get every second of a co-op game, up to `MAX_COOP_SPEED_UP` times
+/// their usual speed.
+const COOP_SPEED_UP_PER_SECOND: f64 = 0.01;
+const MAX_COOP_SPEED_UP: f64 = 2.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// How the ball is drawn, and what its collisions with the paddles test against.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BallShape {
+    #[default]
+    Square,
+    /// The circle inside the ball's square.
+    Circle,
+}
+
+/// Where a game stands once both sides are a point short of the target, when winning takes a
+/// two-point lead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overtime {
+    Deuce,
+    Advantage(Side),
+    /// Level when time ran out with `--time-limit`, so the next point wins.
+    SuddenDeath,
+}
+
+/// Something that happened during an update, for the caller to play sounds, shake the view or
+/// log from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameEvent {
+    /// The paddle on `side` returned a ball, sending it off at `speed`.
+    PaddleHit {
+        side: Side,
+        speed: f64,
+    },
+    WallBounce,
+    /// `side` won a point.
+    Scored {
+        side: Side,
+    },
+    /// The game was decided, though a series may go on.
+    GameOver {
+        winner: Side,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Player {
+    pub vertices: Vec<Vertex>,
+    /// Vertical speed, in screen units per second, positive going up.
+    pub velocity: f64,
+}
+
+impl Player {
+    pub fn center(&self) -> Vec2 {
+        let (min, max) = bounds(&self.vertices);
+        min.midpoint(max)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Score {
+    pub left: u32,
+    pub right: u32,
+}
+
+/// A best-of-N series, counting the games each side has won.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Match {
+    pub games_to_win: u32,
+    pub left_games: u32,
+    pub right_games: u32,
+}
+
+impl Match {
+    /// A series decided by a majority of `games`, a single game for 1.
+    pub fn best_of(games: u32) -> Self {
+        Match {
+            games_to_win: games / 2 + 1,
+            left_games: 0,
+            right_games: 0,
+        }
+    }
+
+    /// The side that has won the series, if either has.
+    pub fn winner(&self) -> Option<Side> {
+        if self.left_games >= self.games_to_win {
+            Some(Side::Left)
+        } else if self.right_games >= self.games_to_win {
+            Some(Side::Right)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for Match {
+    fn default() -> Self {
+        Match::best_of(1)
+    }
+}
+
+/// How well the computer plays a paddle.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AiConfig {
+    /// Top paddle speed, in screen units per second.
+    pub max_speed: f64,
+    /// How far the ball may drift from the paddle's centre before the paddle reacts.
+    pub deadzone: f64,
+}
+
+impl AiConfig {
+    pub const EASY: AiConfig = AiConfig {
+        max_speed: 0.5,
+        deadzone: 0.15,
+    };
+    pub const MEDIUM: AiConfig = AiConfig {
+        max_speed: 1.0,
+        deadzone: 0.03,
+    };
+    pub const HARD: AiConfig = AiConfig {
+        max_speed: 1.4,
+        deadzone: 0.,
+    };
+}
+
+/// Which paddle keys are currently held down, shared between the key callback and the game loop.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct InputState {
+    pub p1_up: bool,
+    pub p1_down: bool,
+    pub p2_up: bool,
+    pub p2_down: bool,
+    /// Set on a P press, cleared by the game loop once handled.
+    pub toggle_pause: bool,
+    /// Set on an F5 press, cleared by the game loop once handled.
+    pub save_requested: bool,
+    /// Set on an F9 press, cleared by the game loop once handled.
+    pub load_requested: bool,
+    /// Set on an F11 press, cleared by the game loop once handled.
+    pub toggle_fullscreen: bool,
+    /// Set on an F12 press, cleared by the game loop once handled.
+    pub screenshot_requested: bool,
+    /// Set on a `[` press, cleared by the game loop once handled.
+    pub slow_down: bool,
+    /// Set on a `]` press, cleared by the game loop once handled.
+    pub speed_up: bool,
+    /// Set on a backtick press, cleared by the game loop once handled.
+    pub toggle_debug: bool,
+    /// Set on any key press, cleared by the game loop once handled.
+    pub any_key: bool,
+    /// Set on an F3 press, cleared by the game loop once handled.
+    #[serde(default)]
+    pub toggle_boxes: bool,
+    /// Held to charge the serve during the countdown.
+    #[serde(default)]
+    pub charge_serve: bool,
+    /// Set on an O press, cleared by the game loop once handled.
+    #[serde(default)]
+    pub open_options: bool,
+    /// Set on an R press, cleared by the game loop once handled.
+    #[serde(default)]
+    pub restart: bool,
+    /// The newest key pressed for the options menu, cleared by the game loop once handled.
+    #[serde(skip)]
+    pub menu_key: Option<MenuKey>,
+}
+
+impl InputState {
+    /// The same input with player 2's up and down swapped, for `--p2-invert`.
+    #[must_use]
+    pub fn with_p2_inverted(self) -> Self {
+        InputState {
+            p2_up: self.p2_down,
+            p2_down: self.p2_up,
+            ..self
+        }
+    }
+}
+
+/// Holds inputs back a fixed number of steps, as a stand-in for network latency with
+/// `--input-delay`.
+pub struct InputDelay {
+    queue: VecDeque<InputState>,
+    steps: usize,
+}
+
+impl InputDelay {
+    pub fn new(steps: usize) -> Self {
+        InputDelay {
+            queue: VecDeque::with_capacity(steps + 1),
+            steps,
+        }
+    }
+
+    /// Queues `input` and returns the one from `steps` calls ago, nothing held until then.
+    pub fn delay(&mut self, input: InputState) -> InputState {
+        self.queue.push_back(input);
+        if self.queue.len() > self.steps {
+            self.queue.pop_front().unwrap_or_default()
+        } else {
+            InputState::default()
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Ball {
+    pub vertices: Vec<Vertex>,
+    /// Speed in screen units per second, never above `MAX_BALL_SPEED`.
+    pub velocity: f64,
+    #[serde(with = "wrap64")]
+    pub velocity_direction: Wrap64,
+    /// Constant acceleration, in screen units per second squared, applied every step along
+    /// `acceleration_direction`.
+    pub acceleration: f64,
+    #[serde(with = "wrap64")]
+    pub acceleration_direction: Wrap64,
+    /// Speed a ramped serve is working up to, `None` once it's there or without `--serve-accel`.
+    #[serde(default)]
+    pub ramp_target: Option<f64>,
+    /// The paddle that last returned the ball, credited with the power-ups it picks up.
+    #[serde(default)]
+    pub last_hit: Option<Side>,
+    /// Seconds left for a ball split off by a power-up, `None` for balls that stay all match.
+    #[serde(default)]
+    pub lifetime: Option<f64>,
+    /// How fast the heading turns, in radians per second, positive turning anticlockwise.
+    #[serde(default)]
+    pub spin: f64,
+}
+
+/// What picking up a power-up does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PowerUpKind {
+    /// Stretches the paddle that last hit the ball.
+    BigPaddle,
+    /// Speeds up every ball.
+    FastBall,
+    /// Slows down every ball.
+    SlowBall,
+    /// Splits off another ball.
+    ExtraBall,
+}
+
+impl PowerUpKind {
+    const ALL: [PowerUpKind; 4] = [
+        PowerUpKind::BigPaddle,
+        PowerUpKind::FastBall,
+        PowerUpKind::SlowBall,
+        PowerUpKind::ExtraBall,
+    ];
+
+    /// Each kind gets its own colour, so players can tell them apart.
+    fn color(self) -> [f32; 3] {
+        match self {
+            PowerUpKind::BigPaddle => [0.3, 0.9, 0.4],
+            PowerUpKind::FastBall => [1., 0.35, 0.25],
+            PowerUpKind::SlowBall => [0.35, 0.6, 1.],
+            PowerUpKind::ExtraBall => [1., 0.85, 0.25],
+        }
+    }
+}
+
+/// A pickup waiting on the field for a ball to run into it.
+#[derive(Serialize, Deserialize)]
+pub struct PowerUp {
+    pub kind: PowerUpKind,
+    pub vertices: Vec<Vertex>,
+}
+
+/// A picked-up power-up still in effect.
+#[derive(Serialize, Deserialize)]
+enum Effect {
+    /// A stretched paddle, and the half-height it shrinks back to.
+    BigPaddle { side: Side, half_height: f64 },
+    /// Every ball moving this many times as fast.
+    BallSpeed(f64),
+}
+
+#[derive(Serialize, Deserialize)]
+struct ActiveEffect {
+    effect: Effect,
+    /// Seconds until the effect wears off.
+    remaining: f64,
+}
+
+impl Ball {
+    pub fn center(&self) -> Vec2 {
+        let (min, max) = bounds(&self.vertices);
+        min.midpoint(max)
+    }
+
+    /// Velocity as a vector, in screen units per second.
+    fn velocity_vector(&self) -> Vec2 {
+        Vec2::from_direction(self.velocity_direction) * self.velocity
+    }
+
+    /// Adds `dt` seconds of acceleration to the velocity, capping the speed at
+    /// `MAX_BALL_SPEED` so the ball can't outrun the collision checks.
+    fn apply_acceleration(&mut self, dt: f64) {
+        let acceleration = Vec2::from_direction(self.acceleration_direction) * self.acceleration;
+        let velocity = self.velocity_vector() + acceleration * dt;
+
+        self.velocity = velocity.length().min(MAX_BALL_SPEED);
+        // A ball brought to a stop keeps its heading
+        if let Some(heading) = velocity.normalize() {
+            self.velocity_direction = heading.direction();
+        }
+    }
+
+    /// Tilts a near-vertical heading towards horizontal until the ball moves sideways at
+    /// `MIN_HORIZONTAL_SPEED`, keeping its speed and which way it was going on both axes.
+    fn keep_horizontal_speed(&mut self) {
+        let velocity = self.velocity_vector();
+        if velocity.x.abs() >= MIN_HORIZONTAL_SPEED {
+            return;
+        }
+        let x = MIN_HORIZONTAL_SPEED.min(self.velocity).copysign(velocity.x);
+        let y = (self.velocity.powi(2) - x * x)
+            .max(0.)
+            .sqrt()
+            .copysign(velocity.y);
+        if let Some(heading) = Vec2::new(x, y).normalize() {
+            self.velocity_direction = heading.direction();
+        }
+    }
+
+    /// Turns the heading by the spin, which dies down a little every step. The ball never curves
+    /// steeper than a paddle can send it, or it could end up going back and forth between the walls.
+    fn apply_spin(&mut self, dt: f64) {
+        let heading = self.velocity_direction + Wrap64::wrap(self.spin * dt);
+        if heading.sin().abs() <= MAX_PADDLE_ANGLE.sin() {
+            self.velocity_direction = heading;
+        } else {
+            self.spin = 0.;
+        }
+        self.spin *= (-dt / SPIN_DECAY_TIME).exp();
+    }
+
+    /// Advances the ball by `dt` seconds, folding the acceleration and spin into the velocity
+    /// first.
+    fn integrate(&mut self, dt: f64) {
+        // A ramped serve speeds up along the heading, whatever the walls or spin did to it
+        if self.ramp_target.is_some() {
+            self.acceleration_direction = self.velocity_direction;
+        }
+        self.apply_acceleration(dt);
+        if let Some(target) = self.ramp_target.filter(|&target| self.velocity >= target) {
+            self.velocity = target;
+            self.end_serve_ramp();
+        }
+        self.apply_spin(dt);
+        let offset = self.velocity_vector() * dt;
+        translate(&mut self.vertices, offset);
+    }
+
+    /// Whether the position and motion are all real numbers. A NaN anywhere would otherwise
+    /// carry on into every later step, and the ball would vanish without ever scoring. Only
+    /// release builds can get a NaN heading, debug builds panic on making one.
+    fn is_finite(&self) -> bool {
+        let center = self.center();
+        center.x.is_finite()
+            && center.y.is_finite()
+            && self.velocity.is_finite()
+            && self.velocity_direction.val().is_finite()
+            && self.spin.is_finite()
+    }
+
+    /// Gives a serve its `speed`, right away or, with `ramp`, working up to it from
+    /// `SERVE_RAMP_START_SPEED` over `SERVE_RAMP_TIME`.
+    fn set_serve_speed(&mut self, speed: f64, ramp: bool) {
+        self.end_serve_ramp();
+        if ramp {
+            self.velocity = SERVE_RAMP_START_SPEED.min(speed);
+            self.acceleration = (speed - self.velocity) / SERVE_RAMP_TIME;
+            self.acceleration_direction = self.velocity_direction;
+            self.ramp_target = Some(speed);
+        } else {
+            self.velocity = speed;
+        }
+    }
+
+    /// Stops speeding up, leaving paddle hits to change the speed from here on.
+    fn end_serve_ramp(&mut self) {
+        self.acceleration = 0.;
+        self.ramp_target = None;
+    }
+
+    /// Puts the ball back on `ball_spawn` and sends it off towards a random side, ramping up
+    /// to speed with `ramp`.
+    fn respawn(&mut self, ball_spawn: &[Vertex], side: Option<Side>, ramp: bool, rng: &mut StdRng) {
+        self.vertices = ball_spawn.to_vec();
+        self.velocity_direction = serve_direction(rng, side);
+        self.set_serve_speed(SERVE_SPEED, ramp);
+        self.spin = 0.;
+        self.last_hit = None;
+        debug!(
+            "Serving at {:.1} degrees",
+            self.velocity_direction.val().to_degrees()
+        );
+    }
+}
+
+/// Everything that makes up a match, independent of the window and the GPU.
+#[derive(Serialize, Deserialize)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct GameState {
+    pub player_1: Player,
+    pub player_2: Player,
+    pub balls: Vec<Ball>,
+    pub score: Score,
+    /// Points a player needs to win.
+    pub target_score: u32,
+    /// Games won so far in the series this game is part of.
+    #[serde(default)]
+    pub series: Match,
+    /// Distance from the middle of the field to the goal lines on either side. The walls are
+    /// always at 1 and -1.
+    #[serde(default = "default_half_width")]
+    pub half_width: f64,
+    /// Seconds left before the balls start moving; they stay frozen until then.
+    pub serve_timer: f64,
+    /// What `serve_timer` is reset to at every serve.
+    pub serve_delay: f64,
+    /// Seconds each game lasts before whoever is ahead wins, `None` playing to the target score
+    /// alone.
+    #[serde(default)]
+    pub time_limit: Option<f64>,
+    /// Seconds played in the current game. Only updates with the balls in play advance it, so
+    /// pausing and serve countdowns stop the clock.
+    #[serde(default)]
+    pub elapsed: f64,
+    /// Multiplier on every ball's speed, on top of any power-up.
+    #[serde(default = "default_ball_speed")]
+    pub ball_speed: f64,
+    /// Seconds the serve has been charged for; the countdown waits while it builds up.
+    #[serde(default)]
+    pub serve_charge: f64,
+    /// Whether the serve key has been up since the countdown started, so the Space press that
+    /// starts a match doesn't also start a charge.
+    #[serde(default)]
+    charge_ready: bool,
+    /// How the computer drives player 2, if it does.
+    pub ai: Option<AiConfig>,
+    /// How the computer drives player 1, if it does, as in the attract-mode demo.
+    #[serde(default)]
+    pub p1_ai: Option<AiConfig>,
+    /// The side every serve heads towards, or `None` for a coin toss each time.
+    #[serde(default)]
+    pub serve_side: Option<Side>,
+    /// Whether serves start slow and speed up over their first second.
+    #[serde(default)]
+    pub serve_accel: bool,
+    #[serde(default)]
+    pub ball_shape: BallShape,
+    /// Whether player 1 practises alone against a wall in place of player 2. Misses still count
+    /// as points for the right, but nobody ever wins.
+    #[serde(default)]
+    pub practice_wall: bool,
+    /// Whether both players keep one rally going together, the balls speeding up as it lasts.
+    /// The first miss ends the match.
+    #[serde(default)]
+    pub coop: bool,
+    /// Returns so far in the current rally.
+    #[serde(default)]
+    pub rally: u32,
+    /// The longest rally of the match.
+    #[serde(default)]
+    pub best_rally: u32,
+    /// Seconds between power-up spawns, `None` leaving them out.
+    #[serde(default)]
+    pub power_up_interval: Option<f64>,
+    /// Pickups waiting on the field.
+    #[serde(default)]
+    pub power_ups: Vec<PowerUp>,
+    /// Seconds of play since the last power-up spawned.
+    #[serde(default)]
+    since_power_up: f64,
+    #[serde(default)]
+    effects: Vec<ActiveEffect>,
+    /// Decides every serve. Left out of saves and snapshots, a loaded match gets a fresh, unseeded
+    /// generator from [`load_state`], and the client never serves at all.
+    #[serde(skip, default = "placeholder_rng")]
+    rng: StdRng,
+    ball_spawn: Vec<Vertex>,
+}
+
+impl GameState {
+    /// Sets up a match with `ball_count` balls resting around `ball_spawn` until [`Self::serve`].
+    /// Balls that go out are re-served from `ball_spawn` itself. The same `seed` and inputs
+    /// always play out the same match.
+    pub fn new(
+        player_1: Player,
+        player_2: Player,
+        ball_spawn: Vec<Vertex>,
+        ball_count: usize,
+        ai: Option<AiConfig>,
+        seed: u64,
+    ) -> Self {
+        let balls = rack_balls(&ball_spawn, ball_count);
+
+        GameState {
+            player_1,
+            player_2,
+            balls,
+            score: Score { left: 0, right: 0 },
+            target_score: GAME_TARGET_SCORE,
+            series: Match::default(),
+            half_width: default_half_width(),
+            serve_timer: 0.,
+            serve_delay: SERVE_DELAY,
+            time_limit: None,
+            elapsed: 0.,
+            ball_speed: default_ball_speed(),
+            serve_charge: 0.,
+            charge_ready: false,
+            ai,
+            p1_ai: None,
+            serve_side: None,
+            serve_accel: false,
+            ball_shape: BallShape::Square,
+            practice_wall: false,
+            coop: false,
+            rally: 0,
+            best_rally: 0,
+            power_up_interval: None,
+            power_ups: Vec::new(),
+            since_power_up: 0.,
+            effects: Vec::new(),
+            rng: StdRng::seed_from_u64(seed),
+            ball_spawn,
+        }
+    }
+
+    /// Sends every ball off towards a random side once the serve countdown runs out.
+    pub fn serve(&mut self) {
+        self.serve_timer = self.serve_delay;
+        self.charge_ready = false;
+        for ball in &mut self.balls {
+            ball.velocity_direction = serve_direction(&mut self.rng, self.serve_side);
+            ball.set_serve_speed(SERVE_SPEED, self.serve_accel);
+            ball.spin = 0.;
+            debug!(
+                "Serving at {:.1} degrees",
+                ball.velocity_direction.val().to_degrees()
+            );
+        }
+    }
+
+    /// How far the serve is charged, from 0 to 1 at full power.
+    pub fn serve_charge_fraction(&self) -> f64 {
+        (self.serve_charge / MAX_SERVE_CHARGE).min(1.)
+    }
+
+    /// Runs the serve countdown, which stops while `charging` builds up a faster serve. Returns
+    /// true when a charged serve is released and the balls should set off right away.
+    fn count_down_serve(&mut self, dt: f64, charging: bool) -> bool {
+        if charging && self.charge_ready {
+            self.serve_charge += dt;
+            return false;
+        }
+        self.charge_ready = !charging;
+
+        if self.serve_charge > 0. {
+            let speed =
+                SERVE_SPEED + (CHARGED_SERVE_SPEED - SERVE_SPEED) * self.serve_charge_fraction();
+            // Balls a paddle has already hit keep the speed they had
+            for ball in self.balls.iter_mut().filter(|ball| ball.last_hit.is_none()) {
+                ball.set_serve_speed(speed, self.serve_accel);
+            }
+            self.serve_charge = 0.;
+            self.serve_timer = 0.;
+            return true;
+        }
+
+        self.serve_timer -= dt;
+        false
+    }
+
+    /// Moves both paddles, from `input` or by the computer, and keeps them on the field.
+    fn move_players(&mut self, dt: f64, input: &InputState) {
+        match self.p1_ai {
+            Some(ai) => {
+                if let Some(ball) = ai_target(&self.balls, Side::Left) {
+                    ai_move_paddle(&mut self.player_1, ball, ai, dt);
+                }
+            }
+            None => move_paddle(&mut self.player_1, input.p1_up, input.p1_down, dt),
+        }
+        match self.ai {
+            Some(ai) => {
+                if let Some(ball) = ai_target(&self.balls, Side::Right) {
+                    ai_move_paddle(&mut self.player_2, ball, ai, dt);
+                }
+            }
+            None => move_paddle(&mut self.player_2, input.p2_up, input.p2_down, dt),
+        }
+        sanitize(&mut self.player_1);
+        sanitize(&mut self.player_2);
+    }
+
+    /// Advances the match by `dt` seconds: paddles, balls, collisions and scoring. Returns what
+    /// happened along the way, in order.
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    pub fn update(&mut self, dt: f64, input: &InputState) -> Vec<GameEvent> {
+        let decided = self.winner().is_some();
+        let mut events = Vec::new();
+        self.step(dt, input, &mut events);
+        if let Some(winner) = self.winner().filter(|_| !decided) {
+            events.push(GameEvent::GameOver { winner });
+        }
+        events
+    }
+
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    fn step(&mut self, dt: f64, input: &InputState, events: &mut Vec<GameEvent>) {
+        self.move_players(dt, input);
+
+        // Paddles can still be lined up during the countdown, but the balls wait for it
+        if self.serve_timer > 0. && !self.count_down_serve(dt, input.charge_serve) {
+            return;
+        }
+        // Only time spent playing counts against the time limit
+        self.elapsed += dt;
+
+        self.update_power_ups(dt);
+        let ball_dt = dt * self.ball_speed * self.ball_speed_scale() * self.coop_speed_up();
+        let mut pickups = Vec::new();
+
+        for (index, ball) in self.balls.iter_mut().enumerate() {
+            // Caught before integrating, which would spread it to the heading
+            if !ball.is_finite() {
+                warn!("Ball position or velocity is no longer finite, serving it again");
+                ball.respawn(
+                    &self.ball_spawn,
+                    self.serve_side,
+                    self.serve_accel,
+                    &mut self.rng,
+                );
+                self.serve_timer = self.serve_delay;
+                self.charge_ready = false;
+                continue;
+            }
+            let start = ball.center();
+            ball.integrate(ball_dt);
+            if let Some(lifetime) = &mut ball.lifetime {
+                *lifetime -= dt;
+            }
+
+            let side_wall = self.practice_wall.then_some(self.half_width);
+            if bounce_off_walls(ball, side_wall) {
+                events.push(GameEvent::WallBounce);
+            }
+
+            // Checks collision, only against player 1 when the wall stands in for player 2
+            let paddle_count = if self.practice_wall { 1 } else { 2 };
+            for (player, side) in [(&self.player_1, Side::Left), (&self.player_2, Side::Right)]
+                .into_iter()
+                .take(paddle_count)
+            {
+                // A fast ball can skip over a thin paddle in a single step
+                let hit = if ball.velocity > SWEPT_SPEED_THRESHOLD {
+                    ball_crosses_paddle(start, ball, player, self.ball_shape)
+                } else {
+                    ball_hits_paddle(ball, player, self.ball_shape)
+                };
+                if !hit {
+                    continue;
+                }
+
+                if bounce_off_paddle(ball, player) {
+                    events.push(GameEvent::PaddleHit {
+                        side,
+                        speed: ball.velocity,
+                    });
+                    if self.practice_wall || self.coop {
+                        self.rally += 1;
+                        self.best_rally = self.best_rally.max(self.rally);
+                    }
+                }
+            }
+
+            if let Some(power_up) = self
+                .power_ups
+                .iter()
+                .position(|power_up| overlaps(&ball.vertices, &power_up.vertices))
+            {
+                pickups.push((self.power_ups.remove(power_up).kind, index));
+            }
+
+            // Scoring
+            let ball_x = ball.center().x;
+            if !(-self.half_width..=self.half_width).contains(&ball_x) {
+                let side = if ball_x < 0. {
+                    self.score.right += 1;
+                    Side::Right
+                } else {
+                    self.score.left += 1;
+                    Side::Left
+                };
+                // In co-op the rally is the final score, kept up for the game over screen
+                if !self.coop {
+                    self.rally = 0;
+                }
+                events.push(GameEvent::Scored { side });
+
+                // A split-off ball just goes away instead of being served again
+                if ball.lifetime.is_some() {
+                    ball.lifetime = Some(0.);
+                    continue;
+                }
+                ball.respawn(
+                    &self.ball_spawn,
+                    self.serve_side,
+                    self.serve_accel,
+                    &mut self.rng,
+                );
+                self.serve_timer = self.serve_delay;
+                self.charge_ready = false;
+            }
+        }
+
+        for (kind, ball) in pickups {
+            self.apply_power_up(kind, ball);
+        }
+        self.balls
+            .retain(|ball| ball.lifetime.is_none_or(|lifetime| lifetime > 0.));
+    }
+
+    /// Spawns power-ups every `power_up_interval` and wears off the running effects.
+    fn update_power_ups(&mut self, dt: f64) {
+        if let Some(interval) = self.power_up_interval {
+            self.since_power_up += dt;
+            if self.since_power_up >= interval {
+                self.since_power_up -= interval;
+                if self.power_ups.len() < MAX_POWER_UPS {
+                    let kind = PowerUpKind::ALL[self.rng.random_range(0..PowerUpKind::ALL.len())];
+                    // Keep clear of the paddles and the walls
+                    let reach = self.half_width - 0.5;
+                    let center = Vec2::new(
+                        self.rng.random_range(-reach..=reach),
+                        self.rng.random_range(-0.8..=0.8),
+                    );
+                    self.power_ups.push(PowerUp {
+                        kind,
+                        vertices: power_up_vertices(center, kind.color()),
+                    });
+                }
+            }
+        }
+
+        for active in &mut self.effects {
+            active.remaining -= dt;
+        }
+        let (running, expired) = std::mem::take(&mut self.effects)
+            .into_iter()
+            .partition(|active| active.remaining > 0.);
+        self.effects = running;
+        for active in expired {
+            self.end_effect(&active.effect);
+        }
+    }
+
+    /// Undoes what an effect changed when it wears off.
+    fn end_effect(&mut self, effect: &Effect) {
+        if let Effect::BigPaddle { side, half_height } = *effect {
+            resize_paddle(&mut self.player_mut(side).vertices, half_height);
+        }
+    }
+
+    /// Resizes both paddles to `half_height`. A paddle stretched by a power-up stays stretched
+    /// until it wears off, then shrinks back to the new size.
+    pub fn set_paddle_half_height(&mut self, half_height: f64) {
+        for side in [Side::Left, Side::Right] {
+            let stretched = self
+                .effects
+                .iter_mut()
+                .find_map(|active| match &mut active.effect {
+                    Effect::BigPaddle {
+                        side: stretched,
+                        half_height,
+                    } if *stretched == side => Some(half_height),
+                    _ => None,
+                });
+            let size = match stretched {
+                Some(shrink_to) => {
+                    *shrink_to = half_height;
+                    half_height * BIG_PADDLE_SCALE
+                }
+                None => half_height,
+            };
+            resize_paddle(&mut self.player_mut(side).vertices, size);
+        }
+    }
+
+    fn player_mut(&mut self, side: Side) -> &mut Player {
+        match side {
+            Side::Left => &mut self.player_1,
+            Side::Right => &mut self.player_2,
+        }
+    }
+
+    /// Applies a power-up picked up by the ball at `ball`.
+    fn apply_power_up(&mut self, kind: PowerUpKind, ball: usize) {
+        match kind {
+            PowerUpKind::BigPaddle => {
+                // Nobody to credit until the ball has been returned once
+                let Some(side) = self.balls[ball].last_hit else {
+                    return;
+                };
+                // Picking it up again only tops up the time
+                if let Some(active) = self.effects.iter_mut().find(|active| {
+                    matches!(active.effect, Effect::BigPaddle { side: stretched, .. } if stretched == side)
+                }) {
+                    active.remaining = POWER_UP_DURATION;
+                    return;
+                }
+                let player = self.player_mut(side);
+                let (min, max) = bounds(&player.vertices);
+                let half_height = (max.y - min.y) / 2.;
+                resize_paddle(&mut player.vertices, half_height * BIG_PADDLE_SCALE);
+                self.effects.push(ActiveEffect {
+                    effect: Effect::BigPaddle { side, half_height },
+                    remaining: POWER_UP_DURATION,
+                });
+            }
+            PowerUpKind::FastBall | PowerUpKind::SlowBall => {
+                // The newest speed change replaces any other
+                self.effects
+                    .retain(|active| !matches!(active.effect, Effect::BallSpeed(_)));
+                let scale = if kind == PowerUpKind::FastBall {
+                    FAST_BALL_SCALE
+                } else {
+                    SLOW_BALL_SCALE
+                };
+                self.effects.push(ActiveEffect {
+                    effect: Effect::BallSpeed(scale),
+                    remaining: POWER_UP_DURATION,
+                });
+            }
+            PowerUpKind::ExtraBall => {
+                let mut extra = self.balls[ball].clone();
+                extra.velocity_direction = reflect_vertical(extra.velocity_direction);
+                extra.lifetime = Some(POWER_UP_DURATION);
+                self.balls.push(extra);
+            }
+        }
+    }
+
+    /// How many times as fast as their speed the balls are currently moving.
+    fn ball_speed_scale(&self) -> f64 {
+        self.effects
+            .iter()
+            .map(|active| match active.effect {
+                Effect::BallSpeed(scale) => scale,
+                Effect::BigPaddle { .. } => 1.,
+            })
+            .product()
+    }
+
+    /// How many times as fast the balls move for how long the co-op rally has gone on.
+    fn coop_speed_up(&self) -> f64 {
+        if self.coop {
+            (1. + COOP_SPEED_UP_PER_SECOND * self.elapsed).min(MAX_COOP_SPEED_UP)
+        } else {
+            1.
+        }
+    }
+
+    /// The side that reached the target score with a lead of at least two points, if any.
+    /// Once time runs out with a limit set, whoever is ahead wins. In co-op the first miss
+    /// ends the match, put down to the side the ball got out on.
+    pub fn winner(&self) -> Option<Side> {
+        let Score { left, right } = self.score;
+        if self.practice_wall {
+            return None;
+        }
+        if self.coop {
+            return match (left, right) {
+                (0, 0) => None,
+                (_, 0) => Some(Side::Left),
+                _ => Some(Side::Right),
+            };
+        }
+        if self.time_left().is_some_and(|left| left <= 0.) {
+            return match left.cmp(&right) {
+                Ordering::Greater => Some(Side::Left),
+                Ordering::Less => Some(Side::Right),
+                Ordering::Equal => None,
+            };
+        }
+        if left.max(right) < self.target_score || left.abs_diff(right) < 2 {
+            None
+        } else if left > right {
+            Some(Side::Left)
+        } else {
+            Some(Side::Right)
+        }
+    }
+
+    /// Deuce or the side with the advantage, once both sides are a point short of the target and
+    /// neither has won yet.
+    pub fn overtime(&self) -> Option<Overtime> {
+        let Score { left, right } = self.score;
+        if self.time_left().is_some_and(|left| left <= 0.) {
+            return (left == right && !self.practice_wall).then_some(Overtime::SuddenDeath);
+        }
+        if left.min(right) + 1 < self.target_score || self.winner().is_some() {
+            return None;
+        }
+        Some(match left.cmp(&right) {
+            Ordering::Equal => Overtime::Deuce,
+            Ordering::Greater => Overtime::Advantage(Side::Left),
+            Ordering::Less => Overtime::Advantage(Side::Right),
+        })
+    }
+
+    /// Seconds until the game's time limit runs out, if it has one.
+    pub fn time_left(&self) -> Option<f64> {
+        self.time_limit.map(|limit| (limit - self.elapsed).max(0.))
+    }
+
+    /// The `(min, max)` corners of every box collisions are checked against: the paddles in play
+    /// and each ball.
+    pub fn collision_boxes(&self) -> Vec<(Vec2, Vec2)> {
+        let paddles: &[&Player] = if self.practice_wall {
+            &[&self.player_1]
+        } else {
+            &[&self.player_1, &self.player_2]
+        };
+        paddles
+            .iter()
+            .map(|player| bounds(&player.vertices))
+            .chain(self.balls.iter().map(|ball| bounds(&ball.vertices)))
+            .collect()
+    }
+
+    /// Credits the finished game to its winner in the series, then starts the next game from
+    /// 0 - 0 unless that decided the series. Returns the series winner once there is one.
+    pub fn finish_game(&mut self) -> Option<Side> {
+        match self.winner()? {
+            Side::Left => self.series.left_games += 1,
+            Side::Right => self.series.right_games += 1,
+        }
+        if let Some(winner) = self.series.winner() {
+            return Some(winner);
+        }
+
+        debug!(
+            "Games: {} - {}",
+            self.series.left_games, self.series.right_games
+        );
+        // Every game starts afresh, without the last one's split-off balls or power-ups
+        self.score = Score { left: 0, right: 0 };
+        self.elapsed = 0.;
+        let ball_count = self
+            .balls
+            .iter()
+            .filter(|ball| ball.lifetime.is_none())
+            .count();
+        self.balls = rack_balls(&self.ball_spawn, ball_count);
+        self.power_ups.clear();
+        for active in std::mem::take(&mut self.effects) {
+            self.end_effect(&active.effect);
+        }
+        self.serve();
+        None
+    }
+}
+
+/// Writes the whole match to `path` as JSON.
+///
+/// # Errors
+///
+/// If the match can't be encoded or the file can't be written.
+pub fn save_state(path: &Path, state: &GameState) -> std::io::Result<()> {
+    let json = serde_json::to_string_pretty(state).map_err(std::io::Error::other)?;
+    std::fs::write(path, json)
+}
+
+/// Reads back a match written by [`save_state`].
+///
+/// # Errors
+///
+/// If the file can't be read or doesn't hold a match.
+pub fn load_state(path: &Path) -> std::io::Result<GameState> {
+    let json = std::fs::read_to_string(path)?;
+    let mut state: GameState = serde_json::from_str(&json).map_err(std::io::Error::other)?;
+    state.rng = StdRng::from_os_rng();
+    Ok(state)
+}
+
+/// Stands in for the generator skipped when deserializing, cheap enough to make for every
+/// snapshot the client receives.
+fn placeholder_rng() -> StdRng {
+    StdRng::seed_from_u64(0)
+}
+
+/// Stores angles as their value in radians, since `radians` has no serde support.
+mod wrap64 {
+    use radians::Wrap64;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    pub fn serialize<S: Serializer>(angle: &Wrap64, serializer: S) -> Result<S::Ok, S::Error> {
+        angle.val().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Wrap64, D::Error> {
+        let val = f64::deserialize(deserializer)?;
+        if val.is_finite() {
+            Ok(Wrap64::wrap(val))
+        } else {
+            Err(serde::de::Error::custom("angle must be finite"))
+        }
+    }
+}
+
+fn coin_toss(rng: &mut StdRng, probability: f64) -> bool {
+    rng.random_bool(probability)
+}
+
+/// Speeds a paddle up in the held direction, or slows it down when no direction is held
+/// (pressing both cancels out), then moves it.
+fn move_paddle(player: &mut Player, up: bool, down: bool, dt: f64) {
+    if up == down {
+        let slowdown = PADDLE_DECELERATION * dt;
+        player.velocity -= player.velocity.clamp(-slowdown, slowdown);
+    } else {
+        let direction = if up { 1. } else { -1. };
+        player.velocity += direction * PADDLE_ACCELERATION * dt;
+    }
+    player.velocity = player.velocity.clamp(-PADDLE_SPEED, PADDLE_SPEED);
+
+    translate(&mut player.vertices, Vec2::new(0., player.velocity * dt));
+}
+
+/// The ball the paddle on `side` should chase: the closest one heading its way, or any ball
+/// otherwise.
+fn ai_target(balls: &[Ball], side: Side) -> Option<&Ball> {
+    // Distances are measured towards the paddle, so the largest is the closest
+    let towards = match side {
+        Side::Left => -1.,
+        Side::Right => 1.,
+    };
+    balls
+        .iter()
+        .filter(|ball| ball.velocity_direction.cos() * towards > 0.)
+        .max_by(|a, b| (a.center().x * towards).total_cmp(&(b.center().x * towards)))
+        .or(balls.first())
+}
+
+/// Nudges the paddle towards the ball's height, unless it is already within the dead-zone.
+fn ai_move_paddle(player: &mut Player, ball: &Ball, ai: AiConfig, dt: f64) {
+    let (min, max) = bounds(&player.vertices);
+    let offset = ball.center().y - min.midpoint(max).y;
+    let step = ai.max_speed * dt;
+    let movement = if offset.abs() > ai.deadzone {
+        offset.clamp(-step, step)
+    } else {
+        0.
+    };
+    translate(&mut player.vertices, Vec2::new(0., movement));
+    // Kept up to date for the spin the paddle puts on the ball
+    player.velocity = movement / dt;
+}
+
+/// Pulls a paddle that crossed the top or bottom edge back onto the field, stopping it there.
+fn sanitize(player: &mut Player) {
+    let (min, max) = bounds(&player.vertices);
+    if max.y > 1. || min.y < -1. {
+        player.velocity = 0.;
+    }
+    clamp_paddle(&mut player.vertices);
+}
+
+/// Shifts paddle vertices that cross the top or bottom edge back inside `[-1, 1]`.
+fn clamp_paddle(vertices: &mut [Vertex]) {
+    let (min, max) = bounds(vertices);
+    if max.y > 1. {
+        translate(vertices, Vec2::new(0., 1. - max.y));
+    } else if min.y < -1. {
+        // Paddles are shorter than the field, so they can't cross both edges at once
+        translate(vertices, Vec2::new(0., -1. - min.y));
+    }
+}
+
+/// Stretches or shrinks a paddle around its centre to `half_height`, at most the whole field.
+#[allow(clippy::cast_possible_truncation)]
+fn resize_paddle(vertices: &mut [Vertex], half_height: f64) {
+    let (min, max) = bounds(vertices);
+    let center = min.midpoint(max).y;
+    let scale = half_height.min(1.) / ((max.y - min.y) / 2.);
+    for vertex in &mut *vertices {
+        let y = center + (f64::from(vertex.position[1]) - center) * scale;
+        vertex.position[1] = y as f32;
+    }
+    clamp_paddle(vertices);
+}
+
+/// A power-up pickup's quad around `center`.
+#[allow(clippy::cast_possible_truncation)]
+fn power_up_vertices(center: Vec2, color: [f32; 3]) -> Vec<Vertex> {
+    let half = POWER_UP_HALF_SIZE;
+    [
+        [center.x - half, center.y + half],
+        [center.x - half, center.y - half],
+        [center.x + half, center.y + half],
+        [center.x + half, center.y - half],
+    ]
+    .into_iter()
+    .zip(QUAD_UVS)
+    .map(|([x, y], uv)| Vertex {
+        position: [x as f32, y as f32, 0.],
+        color,
+        uv,
+    })
+    .collect()
+}
+
+/// Sends a ball that touched the paddle back the other way, with the angle set by where it struck
+/// and spin from the paddle's movement. Returns false if the ball was already heading away, as
+/// after a hit on an earlier step.
+fn bounce_off_paddle(ball: &mut Ball, player: &Player) -> bool {
+    let (paddle_min, paddle_max) = bounds(&player.vertices);
+    let (ball_min, ball_max) = bounds(&ball.vertices);
+    let paddle_on_left = paddle_min.x + paddle_max.x < 0.;
+    let towards_paddle = Vec2::new(if paddle_on_left { -1. } else { 1. }, 0.);
+    let returned = ball.velocity_vector().dot(towards_paddle) > 0.;
+    if returned {
+        let paddle_center = paddle_min.midpoint(paddle_max);
+        let half_height = (paddle_max.y - paddle_min.y) / 2.;
+        let offset = (ball.center().y - paddle_center.y) / half_height;
+        ball.velocity_direction = english_direction(offset, paddle_on_left);
+        ball.velocity = (ball.velocity * HIT_SPEEDUP).min(MAX_BALL_SPEED);
+        // Curves the ball the way the paddle was moving
+        let spin = (player.velocity * SPIN_PER_PADDLE_SPEED).clamp(-MAX_SPIN, MAX_SPIN);
+        ball.spin = if paddle_on_left { spin } else { -spin };
+        ball.last_hit = Some(if paddle_on_left {
+            Side::Left
+        } else {
+            Side::Right
+        });
+    }
+
+    // Push the ball back out so the next step doesn't register the same hit
+    let nudge = if paddle_on_left {
+        paddle_max.x - ball_min.x
+    } else {
+        paddle_min.x - ball_max.x
+    };
+    translate(&mut ball.vertices, Vec2::new(nudge, 0.));
+
+    returned
+}
+
+/// Bounces the ball off the top and bottom walls, and off a wall at `side_wall` on the right if
+/// there is one, moving it back onto the field. Returns whether it changed direction.
+fn bounce_off_walls(ball: &mut Ball, side_wall: Option<f64>) -> bool {
+    let mut bounced = false;
+    let (ball_min, ball_max) = bounds(&ball.vertices);
+    if ball_max.y > 1. {
+        if ball.velocity_direction.sin() > 0. {
+            ball.velocity_direction =
+                limit_steepness(reflect_vertical(ball.velocity_direction), MAX_WALL_ANGLE);
+            ball.keep_horizontal_speed();
+            bounced = true;
+        }
+        translate(&mut ball.vertices, Vec2::new(0., 1. - ball_max.y));
+    }
+
+    if ball_min.y < -1. {
+        if ball.velocity_direction.sin() < 0. {
+            ball.velocity_direction =
+                limit_steepness(reflect_vertical(ball.velocity_direction), MAX_WALL_ANGLE);
+            ball.keep_horizontal_speed();
+            bounced = true;
+        }
+        translate(&mut ball.vertices, Vec2::new(0., -1. - ball_min.y));
+    }
+
+    if let Some(wall_x) = side_wall.filter(|&wall_x| ball_max.x > wall_x) {
+        if ball.velocity_direction.cos() > 0. {
+            ball.velocity_direction = reflect_horizontal(ball.velocity_direction);
+            bounced = true;
+        }
+        translate(&mut ball.vertices, Vec2::new(wall_x - ball_max.x, 0.));
+    }
+
+    bounced
+}
+
+/// Mirrors a heading off a horizontal wall, flipping its vertical component.
+fn reflect_vertical(dir: Wrap64) -> Wrap64 {
+    -dir
+}
+
+/// Mirrors a heading off a vertical wall, flipping its horizontal component.
+fn reflect_horizontal(dir: Wrap64) -> Wrap64 {
+    Wrap64::HALF_TURN - dir
+}
+
+/// Tilts `dir` back to `max_angle` off horizontal if it's any steeper, still heading the same
+/// way on both axes.
+fn limit_steepness(dir: Wrap64, max_angle: f64) -> Wrap64 {
+    let (sin, cos) = dir.sin_cos();
+    if sin.abs() <= max_angle.sin() {
+        return dir;
+    }
+    Vec2::new(max_angle.cos().copysign(cos), max_angle.sin().copysign(sin)).direction()
+}
+
+/// Heading of a ball returned by a paddle, given where it struck relative to the paddle's
+/// centre (`-1` at the bottom edge, `1` at the top edge).
+fn english_direction(offset: f64, paddle_on_left: bool) -> Wrap64 {
+    let angle = Wrap64::wrap(offset.clamp(-1., 1.) * MAX_PADDLE_ANGLE);
+    if paddle_on_left {
+        angle
+    } else {
+        Wrap64::HALF_TURN - angle
+    }
+}
+
+/// Heading of a serve towards `side`, or a random side without one, up to `SERVE_CONE_DEGREES`
+/// off horizontal.
+fn serve_direction(rng: &mut StdRng, side: Option<Side>) -> Wrap64 {
+    let spread = SERVE_CONE_DEGREES.to_radians();
+    let angle = Wrap64::wrap(rng.random_range(-spread..=spread));
+    let towards_left = match side {
+        Some(side) => side == Side::Left,
+        None => coin_toss(rng, 0.5),
+    };
+    if towards_left {
+        Wrap64::HALF_TURN - angle
+    } else {
+        angle
+    }
+}
+
+/// Returns the `(min, max)` corners of the axis-aligned box around the vertices.
+fn bounds(vertices: &[Vertex]) -> (Vec2, Vec2) {
+    vertices.iter().fold(
+        (
+            Vec2::new(f64::INFINITY, f64::INFINITY),
+            Vec2::new(f64::NEG_INFINITY, f64::NEG_INFINITY),
+        ),
+        |(min, max), vertex| {
+            let (x, y) = (f64::from(vertex.position[0]), f64::from(vertex.position[1]));
+            (
+                Vec2::new(min.x.min(x), min.y.min(y)),
+                Vec2::new(max.x.max(x), max.y.max(y)),
+            )
+        },
+    )
+}
+
+/// `count` balls at rest, stacked vertically around `ball_spawn`.
+#[allow(clippy::cast_precision_loss)]
+fn rack_balls(ball_spawn: &[Vertex], count: usize) -> Vec<Ball> {
+    (0..count)
+        .map(|index| {
+            let mut vertices = ball_spawn.to_vec();
+            let offset = index as f64 - (count - 1) as f64 / 2.;
+            translate(&mut vertices, Vec2::new(0., offset * BALL_SPACING));
+            Ball {
+                vertices,
+                velocity: 0.,
+                velocity_direction: Wrap64::ZERO,
+                acceleration: 0.,
+                acceleration_direction: Wrap64::ZERO,
+                ramp_target: None,
+                last_hit: None,
+                lifetime: None,
+                spin: 0.,
+            }
+        })
+        .collect()
+}
+
+/// A square field, as wide as it is tall.
+fn default_half_width() -> f64 {
+    1.
+}
+
+/// Balls at their normal speed.
+fn default_ball_speed() -> f64 {
+    1.
+}
+
+/// Whether the bounding boxes of two sets of vertices overlap, touching edges included.
+fn overlaps(a: &[Vertex], b: &[Vertex]) -> bool {
+    let (a_min, a_max) = bounds(a);
+    let (b_min, b_max) = bounds(b);
+
+    a_min.x <= b_max.x && a_max.x >= b_min.x && a_min.y <= b_max.y && a_max.y >= b_min.y
+}
+
+/// Overlap test between the ball and the paddle's bounding box, treating the ball as its own
+/// bounding box or the circle inside it depending on `shape`.
+fn ball_hits_paddle(ball: &Ball, player: &Player, shape: BallShape) -> bool {
+    match shape {
+        BallShape::Square => overlaps(&ball.vertices, &player.vertices),
+        BallShape::Circle => {
+            let (ball_min, ball_max) = bounds(&ball.vertices);
+            let (paddle_min, paddle_max) = bounds(&player.vertices);
+            let center = ball_min.midpoint(ball_max);
+            let radius = (ball_max.x - ball_min.x) / 2.;
+            let closest = Vec2::new(
+                center.x.clamp(paddle_min.x, paddle_max.x),
+                center.y.clamp(paddle_min.y, paddle_max.y),
+            );
+            (center - closest).length() <= radius
+        }
+    }
+}
+
+/// Whether the ball's path this step, from `start` to its current centre, crosses the paddle's
+/// face. The face is pushed out by the ball's half-size so touching edges count.
+fn ball_crosses_paddle(start: Vec2, ball: &Ball, player: &Player, shape: BallShape) -> bool {
+    let (ball_min, ball_max) = bounds(&ball.vertices);
+    let (paddle_min, paddle_max) = bounds(&player.vertices);
+    let half_size = (ball_max - ball_min) * 0.5;
+    let end = ball.center();
+
+    // Distances are measured out from the face, positive on the side facing the field
+    let (face, outwards) = if paddle_min.x + paddle_max.x < 0. {
+        (paddle_max.x + half_size.x, 1.)
+    } else {
+        (paddle_min.x - half_size.x, -1.)
+    };
+    let from = (start.x - face) * outwards;
+    let to = (end.x - face) * outwards;
+    // Only a path from in front of the face to on it or behind it crosses it
+    if from <= 0. || to > 0. {
+        return ball_hits_paddle(ball, player, shape);
+    }
+
+    let t = from / (from - to);
+    let y = (start + (end - start) * t).y;
+    (paddle_min.y - half_size.y..=paddle_max.y + half_size.y).contains(&y)
+}
+
+/// Moves every vertex by `offset`.
+#[allow(clippy::cast_possible_truncation)]
+fn translate(vertices: &mut [Vertex], offset: Vec2) {
+    for vertex in vertices {
+        vertex.position[0] += offset.x as f32;
+        vertex.position[1] += offset.y as f32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 1e-9;
+
+    fn assert_direction(actual: Wrap64, expected: Wrap64) {
+        let (actual_sin, actual_cos) = actual.sin_cos();
+        let (expected_sin, expected_cos) = expected.sin_cos();
+        assert!(
+            (actual_sin - expected_sin).abs() < EPSILON
+                && (actual_cos - expected_cos).abs() < EPSILON,
+            "expected {expected:?}, got {actual:?}"
+        );
+    }
+
+    fn paddle_at(center_y: f32) -> Vec<Vertex> {
+        [[-0.8, 0.2], [-0.8, -0.2], [-0.77, 0.2], [-0.77, -0.2]]
+            .map(|[x, y]| Vertex {
+                position: [x, y + center_y, 0.],
+                color: [1., 1., 1.],
+                uv: [0., 0.],
+            })
+            .to_vec()
+    }
+
+    /// Corners of a ball `0.02` across either way from `center`.
+    fn ball_vertices(center: [f32; 2]) -> Vec<Vertex> {
+        QUAD_UVS
+            .map(|[x, y]| Vertex {
+                position: [center[0] + x * 0.02, center[1] + y * 0.02, 0.],
+                color: [1., 1., 1.],
+                uv: [x, y],
+            })
+            .to_vec()
+    }
+
+    /// A lone ball centred on `center`, moving at `velocity` towards `direction`.
+    fn ball_at(center: [f32; 2], velocity: f64, direction: Wrap64) -> Ball {
+        let mut ball = rack_balls(&ball_vertices(center), 1).remove(0);
+        ball.velocity = velocity;
+        ball.velocity_direction = direction;
+        ball
+    }
+
+    /// A two-ball match with power-ups, so a run goes through as much of the physics as possible.
+    fn scripted_game(seed: u64) -> GameState {
+        let mirror = |vertices: Vec<Vertex>| -> Vec<Vertex> {
+            vertices
+                .into_iter()
+                .map(|vertex| Vertex {
+                    position: [-vertex.position[0], vertex.position[1], 0.],
+                    ..vertex
+                })
+                .collect()
+        };
+        let ball_spawn = ball_vertices([0., 0.]);
+
+        let mut game = GameState::new(
+            Player {
+                vertices: paddle_at(0.),
+                velocity: 0.,
+            },
+            Player {
+                vertices: mirror(paddle_at(0.)),
+                velocity: 0.,
+            },
+            ball_spawn,
+            2,
+            Some(AiConfig::EASY),
+            seed,
+        );
+        game.power_up_interval = Some(2.);
+        game.serve();
+        game
+    }
+
+    /// Player 1's keys for step `step` of a scripted run, sweeping the paddle up and down.
+    fn scripted_input(step: usize) -> InputState {
+        InputState {
+            p1_up: step % 240 < 100,
+            p1_down: (120..220).contains(&(step % 240)),
+            ..InputState::default()
+        }
+    }
+
+    /// Player 2's keys for step `step`, going up for a while and then down.
+    fn p2_input(step: usize) -> InputState {
+        InputState {
+            p2_up: step < 60,
+            p2_down: (90..120).contains(&step),
+            ..InputState::default()
+        }
+    }
+
+    fn assert_on_field(vertices: &[Vertex]) {
+        for vertex in vertices {
+            assert!(
+                (-1. ..=1.).contains(&vertex.position[1]),
+                "{:?} is off the field",
+                vertex.position
+            );
+        }
+    }
+
+    #[test]
+    fn paddle_above_top_is_clamped_to_edge() {
+        let mut vertices = paddle_at(1.1);
+        clamp_paddle(&mut vertices);
+        assert_on_field(&vertices);
+        let (_, max) = bounds(&vertices);
+        assert!((max.y - 1.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn paddle_below_bottom_is_clamped_to_edge() {
+        let mut vertices = paddle_at(-1.5);
+        clamp_paddle(&mut vertices);
+        assert_on_field(&vertices);
+        let (min, _) = bounds(&vertices);
+        assert!((min.y + 1.).abs() < 1e-6);
+    }
+
+    // Not a single vertex should move, so exact comparison is the point
+    #[allow(clippy::float_cmp)]
+    #[test]
+    fn centered_paddle_is_unchanged() {
+        let mut vertices = paddle_at(0.);
+        clamp_paddle(&mut vertices);
+        for (vertex, original) in vertices.iter().zip(paddle_at(0.)) {
+            assert_eq!(vertex.position, original.position);
+        }
+    }
+
+    #[test]
+    fn ramped_serve_reaches_its_speed_without_passing_it() {
+        let mut game = scripted_game(1);
+        game.balls.truncate(1);
+        game.serve_accel = true;
+        game.serve_delay = 0.;
+        game.serve();
+
+        // A little past the ramp's second, for rounding in the steps
+        let mut fastest: f64 = 0.;
+        for _ in 0..130 {
+            game.update(1. / 120., &InputState::default());
+            fastest = fastest.max(game.balls[0].velocity);
+            assert!(game.balls[0].velocity <= SERVE_SPEED);
+        }
+        assert!((fastest - SERVE_SPEED).abs() < EPSILON);
+        assert!(game.balls[0].ramp_target.is_none());
+        assert!(game.balls[0].acceleration.abs() < EPSILON);
+    }
+
+    #[test]
+    fn acceleration_never_exceeds_max_speed() {
+        let mut ball = ball_at([0., 0.], SERVE_SPEED, Wrap64::ZERO);
+        ball.acceleration = 10.;
+        ball.acceleration_direction = Wrap64::wrap(0.3);
+
+        for _ in 0..10_000 {
+            ball.apply_acceleration(1. / 120.);
+            assert!(ball.velocity <= MAX_BALL_SPEED);
+        }
+        assert!((ball.velocity - MAX_BALL_SPEED).abs() < EPSILON);
+    }
+
+    #[test]
+    fn spin_curves_the_ball_and_dies_down() {
+        let mut ball = ball_at([0., 0.], SERVE_SPEED, Wrap64::ZERO);
+        ball.spin = MAX_SPIN;
+
+        for _ in 0..1_200 {
+            ball.apply_spin(1. / 120.);
+            assert!(ball.velocity_direction.sin() > 0.);
+            assert!(ball.velocity_direction.sin() <= MAX_PADDLE_ANGLE.sin());
+        }
+        assert!(ball.spin < MAX_SPIN * 1e-3);
+    }
+
+    #[test]
+    fn paddle_edges_send_the_ball_off_at_exactly_the_max_angle() {
+        assert_direction(english_direction(1., true), Wrap64::wrap(MAX_PADDLE_ANGLE));
+        // Past the edge is no steeper than the edge itself
+        assert_direction(
+            english_direction(-1.5, true),
+            Wrap64::wrap(-MAX_PADDLE_ANGLE),
+        );
+        assert_direction(
+            english_direction(3., false),
+            Wrap64::HALF_TURN - Wrap64::wrap(MAX_PADDLE_ANGLE),
+        );
+    }
+
+    #[test]
+    fn wall_bounces_leave_no_steeper_than_the_max_angle() {
+        use std::f64::consts::PI;
+
+        // Right at the limit the reflection is left exact, just past it in any quadrant it's
+        // tilted back onto the limit
+        let at_limit = Wrap64::wrap(MAX_WALL_ANGLE - 1e-6);
+        assert_direction(limit_steepness(at_limit, MAX_WALL_ANGLE), at_limit);
+        for (dir, expected) in [
+            (MAX_WALL_ANGLE + 0.01, MAX_WALL_ANGLE),
+            (-MAX_WALL_ANGLE - 0.01, -MAX_WALL_ANGLE),
+            (PI - MAX_WALL_ANGLE - 0.01, PI - MAX_WALL_ANGLE),
+            (MAX_WALL_ANGLE + 0.01 - PI, MAX_WALL_ANGLE - PI),
+        ] {
+            assert_direction(
+                limit_steepness(Wrap64::wrap(dir), MAX_WALL_ANGLE),
+                Wrap64::wrap(expected),
+            );
+        }
+
+        // A fast ball gets past the horizontal speed floor, but not the angle
+        let mut ball = ball_at([0., 0.99], MAX_BALL_SPEED, Wrap64::wrap(1.55));
+        assert!(bounce_off_walls(&mut ball, None));
+        assert_direction(ball.velocity_direction, Wrap64::wrap(-MAX_WALL_ANGLE));
+    }
+
+    #[test]
+    fn up_right_reflects_to_down_right_at_top_wall() {
+        let dir = Wrap64::wrap(std::f64::consts::FRAC_PI_4);
+        assert_direction(
+            reflect_vertical(dir),
+            Wrap64::wrap(-std::f64::consts::FRAC_PI_4),
+        );
+    }
+
+    #[test]
+    fn down_left_reflects_to_up_left_at_bottom_wall() {
+        let dir = Wrap64::wrap(-3. * std::f64::consts::FRAC_PI_4);
+        assert_direction(
+            reflect_vertical(dir),
+            Wrap64::wrap(3. * std::f64::consts::FRAC_PI_4),
+        );
+    }
+
+    #[test]
+    fn reflection_preserves_horizontal_component() {
+        for val in [0.1, 0.7, 1.5, 2.2, 3., -0.4, -1.9, -2.8] {
+            let dir = Wrap64::wrap(val);
+            let reflected = reflect_vertical(dir);
+            assert!((reflected.cos() - dir.cos()).abs() < EPSILON);
+            assert!((reflected.sin() + dir.sin()).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn same_seed_and_inputs_play_out_identically() {
+        let mut games = [scripted_game(7), scripted_game(7)];
+        for step in 0..5_000 {
+            for game in &mut games {
+                game.update(1. / 120., &scripted_input(step));
+            }
+        }
+
+        let [a, b] = games;
+        assert_eq!(a.score, b.score);
+        assert_eq!(a.balls.len(), b.balls.len());
+        for (a, b) in a.balls.iter().zip(&b.balls) {
+            let bits = |ball: &Ball| {
+                let positions: Vec<_> = ball
+                    .vertices
+                    .iter()
+                    .flat_map(|vertex| vertex.position.map(f32::to_bits))
+                    .collect();
+                (
+                    positions,
+                    ball.velocity.to_bits(),
+                    ball.velocity_direction.val().to_bits(),
+                    ball.spin.to_bits(),
+                )
+            };
+            assert_eq!(bits(a), bits(b));
+        }
+        for (a, b) in [(&a.player_1, &b.player_1), (&a.player_2, &b.player_2)] {
+            assert_eq!(a.velocity.to_bits(), b.velocity.to_bits());
+        }
+    }
+
+    #[test]
+    fn inverted_p2_input_mirrors_the_paddle() {
+        let mut games = [scripted_game(3), scripted_game(3)];
+        for game in &mut games {
+            game.ai = None;
+        }
+        let [normal, inverted] = &mut games;
+        for step in 0..150 {
+            normal.update(1. / 120., &p2_input(step));
+            inverted.update(1. / 120., &p2_input(step).with_p2_inverted());
+            // Both paddles start centred, so mirrored motion keeps them opposite each other
+            let (normal, inverted) = (normal.player_2.center().y, inverted.player_2.center().y);
+            assert!((normal + inverted).abs() < 1e-6, "{normal} vs {inverted}");
+        }
+        assert!(normal.player_2.center().y > 0.1);
+    }
+
+    #[test]
+    fn held_key_moves_the_paddle_the_same_at_any_repeat_rate() {
+        const DT: f64 = 1. / 120.;
+        const HELD_FRAMES: usize = 48;
+
+        // Key events every `events_every` frames, the way the window delivers a press and then
+        // OS repeats, each of which only says the key is still down
+        let distance_held = |events_every: usize| {
+            let mut game = scripted_game(5);
+            game.ai = None;
+            let start = game.player_2.center().y;
+            let mut input = InputState::default();
+            for frame in 0..HELD_FRAMES {
+                if frame % events_every == 0 {
+                    input.p2_up = true;
+                }
+                game.update(DT, &input);
+            }
+            game.player_2.center().y - start
+        };
+
+        let expected = distance_held(1);
+        assert!(expected > 0.1, "{expected}");
+        for events_every in [2, 3, 5, 30] {
+            let distance = distance_held(events_every);
+            assert!(
+                (distance - expected).abs() < EPSILON,
+                "{distance} vs {expected} with an event every {events_every} frames"
+            );
+        }
+    }
+
+    #[test]
+    fn input_delay_shifts_the_paddle_by_exactly_that_many_steps() {
+        const DELAY: usize = 7;
+
+        let mut games = [scripted_game(4), scripted_game(4)];
+        let start = games[1].player_1.center().y;
+        let mut delay = InputDelay::new(DELAY);
+        let mut positions = [Vec::new(), Vec::new()];
+        for step in 0..300 {
+            let [direct, delayed] = &mut games;
+            direct.update(1. / 120., &scripted_input(step));
+            delayed.update(1. / 120., &delay.delay(scripted_input(step)));
+            positions[0].push(direct.player_1.center().y);
+            positions[1].push(delayed.player_1.center().y);
+        }
+
+        let [direct, delayed] = &positions;
+        // The delayed paddle waits where both started
+        assert!(delayed[..DELAY].iter().all(|&y| (y - start).abs() < 1e-6));
+        for (step, y) in direct.iter().enumerate().take(300 - DELAY) {
+            let shifted = delayed[step + DELAY];
+            assert!((shifted - y).abs() < 1e-6, "step {step}: {shifted} vs {y}");
+        }
+    }
+
+    #[test]
+    fn zero_velocity_never_turns_into_nan() {
+        let mut game = scripted_game(6);
+        game.serve_timer = 0.;
+        for ball in &mut game.balls {
+            ball.velocity = 0.;
+            ball.spin = MAX_SPIN;
+        }
+        for _ in 0..120 {
+            game.update(1. / 120., &InputState::default());
+        }
+        assert!(game.balls.iter().all(Ball::is_finite));
+    }
+
+    #[test]
+    fn overflowing_velocity_keeps_a_finite_heading() {
+        let mut ball = ball_at([0., 0.], f64::MAX, Wrap64::wrap(0.3));
+        ball.acceleration = f64::MAX;
+        ball.acceleration_direction = ball.velocity_direction;
+
+        // Adding the two overflows to an infinite vector, which has no heading to take
+        ball.apply_acceleration(1.);
+
+        assert_direction(ball.velocity_direction, Wrap64::wrap(0.3));
+        assert!((ball.velocity - MAX_BALL_SPEED).abs() < EPSILON);
+        assert!(ball.is_finite());
+    }
+
+    #[test]
+    fn nan_ball_is_served_again_without_a_point() {
+        let mut game = scripted_game(6);
+        game.serve_timer = 0.;
+        // What dividing a zero-length velocity by its length gives
+        game.balls[0].velocity = f64::NAN;
+        let score = game.score;
+
+        game.update(1. / 120., &InputState::default());
+
+        let ball = &game.balls[0];
+        assert!(ball.is_finite());
+        assert!(ball.velocity_direction.val().is_finite());
+        assert!((ball.velocity - SERVE_SPEED).abs() < EPSILON);
+        assert!(ball.center().length() < EPSILON);
+        assert_eq!(game.score, score);
+        assert!(game.serve_timer > 0.);
+    }
+
+    #[test]
+    fn forced_serves_always_head_the_chosen_way() {
+        for (side, towards) in [(Side::Left, -1.), (Side::Right, 1.)] {
+            let mut game = scripted_game(8);
+            game.serve_side = Some(side);
+            for _ in 0..50 {
+                game.serve();
+                assert!(game
+                    .balls
+                    .iter()
+                    .all(|ball| ball.velocity_direction.cos() * towards > 0.));
+            }
+
+            // Every point is served again the same way
+            game.serve_timer = 0.;
+            game.balls[0].velocity = f64::NAN;
+            game.update(1. / 120., &InputState::default());
+            assert!(game.balls[0].velocity_direction.cos() * towards > 0.);
+        }
+    }
+
+    #[test]
+    fn resized_paddles_keep_a_power_up_stretch() {
+        let mut game = scripted_game(2);
+        game.effects.push(ActiveEffect {
+            effect: Effect::BigPaddle {
+                side: Side::Right,
+                half_height: 0.2,
+            },
+            remaining: 1.,
+        });
+
+        game.set_paddle_half_height(0.1);
+        let half_height = |player: &Player| {
+            let (min, max) = bounds(&player.vertices);
+            (max.y - min.y) / 2.
+        };
+        assert!((half_height(&game.player_1) - 0.1).abs() < 1e-6);
+        assert!((half_height(&game.player_2) - 0.1 * BIG_PADDLE_SCALE).abs() < 1e-6);
+
+        // Once the stretch wears off it shrinks to the new size
+        game.update_power_ups(1.);
+        assert!((half_height(&game.player_2) - 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn round_ball_misses_a_corner_its_box_would_clip() {
+        let player = Player {
+            vertices: paddle_at(0.),
+            velocity: 0.,
+        };
+        // Just off the paddle's top corner, diagonally
+        let corner = ball_at([-0.75, 0.215], 1., Wrap64::HALF_TURN);
+        assert!(ball_hits_paddle(&corner, &player, BallShape::Square));
+        assert!(!ball_hits_paddle(&corner, &player, BallShape::Circle));
+
+        let face = ball_at([-0.755, 0.], 1., Wrap64::HALF_TURN);
+        assert!(ball_hits_paddle(&face, &player, BallShape::Square));
+        assert!(ball_hits_paddle(&face, &player, BallShape::Circle));
+    }
+
+    #[test]
+    fn near_vertical_bounce_is_tilted_towards_horizontal() {
+        for (val, left) in [(1.56, false), (-1.58, true), (1.6, true), (-1.55, false)] {
+            let mut ball = ball_at([0., 0.], 1., Wrap64::wrap(val));
+            let rising = ball.velocity_direction.sin() > 0.;
+
+            ball.keep_horizontal_speed();
+            let velocity = ball.velocity_vector();
+            assert!((velocity.x.abs() - MIN_HORIZONTAL_SPEED).abs() < EPSILON);
+            assert_eq!(velocity.x < 0., left);
+            assert_eq!(velocity.y > 0., rising);
+            assert!((velocity.length() - 1.).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn shallow_heading_is_left_alone() {
+        let direction = Wrap64::wrap(0.4);
+        let mut ball = ball_at([0., 0.], 1., direction);
+        ball.keep_horizontal_speed();
+        assert_direction(ball.velocity_direction, direction);
+    }
+
+    #[test]
+    fn deuce_needs_a_two_point_lead() {
+        let mut game = scripted_game(1);
+        game.score = Score {
+            left: 10,
+            right: 10,
+        };
+        assert_eq!(game.overtime(), Some(Overtime::Deuce));
+        assert_eq!(game.winner(), None);
+
+        game.score.left = 11;
+        assert_eq!(game.overtime(), Some(Overtime::Advantage(Side::Left)));
+        assert_eq!(game.winner(), None);
+
+        game.score.right = 11;
+        assert_eq!(game.overtime(), Some(Overtime::Deuce));
+        game.score.right = 10;
+
+        game.score.left = 12;
+        assert_eq!(game.winner(), Some(Side::Left));
+        assert_eq!(game.overtime(), None);
+    }
+
+    #[test]
+    fn updates_report_hits_bounces_and_points() {
+        let mut game = scripted_game(3);
+        let mut events = Vec::new();
+        for step in 0..6_000 {
+            events.extend(game.update(1. / 120., &scripted_input(step)));
+        }
+
+        let hits = events
+            .iter()
+            .filter(|event| matches!(event, GameEvent::PaddleHit { .. }))
+            .count();
+        assert!(hits > 0);
+        assert!(events.contains(&GameEvent::WallBounce));
+        let points = |side| {
+            events
+                .iter()
+                .filter(|&&event| event == GameEvent::Scored { side })
+                .count()
+        };
+        assert_eq!(points(Side::Left), game.score.left as usize);
+        assert_eq!(points(Side::Right), game.score.right as usize);
+    }
+
+    #[test]
+    fn deciding_update_reports_the_game_over_once() {
+        let mut game = scripted_game(1);
+        game.time_limit = Some(1. / 60.);
+        game.serve_timer = 0.;
+        game.score = Score { left: 1, right: 0 };
+        let first = game.update(1. / 120., &InputState::default());
+        assert!(!first
+            .iter()
+            .any(|event| matches!(event, GameEvent::GameOver { .. })));
+
+        let second = game.update(1. / 120., &InputState::default());
+        assert!(second.contains(&GameEvent::GameOver { winner: Side::Left }));
+        let third = game.update(1. / 120., &InputState::default());
+        assert!(!third
+            .iter()
+            .any(|event| matches!(event, GameEvent::GameOver { .. })));
+    }
+
+    #[test]
+    fn coop_ends_on_the_first_miss_with_the_rally_as_its_score() {
+        let mut game = scripted_game(3);
+        game.coop = true;
+        let mut events = Vec::new();
+        for step in 0..60_000 {
+            events.extend(game.update(1. / 120., &scripted_input(step)));
+            if game.winner().is_some() {
+                break;
+            }
+        }
+
+        let hits = events
+            .iter()
+            .filter(|event| matches!(event, GameEvent::PaddleHit { .. }))
+            .count();
+        assert_eq!(game.score.left + game.score.right, 1);
+        assert_eq!(game.rally as usize, hits);
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, GameEvent::GameOver { .. })));
+    }
+
+    #[test]
+    fn leader_wins_when_time_runs_out() {
+        let mut game = scripted_game(1);
+        game.time_limit = Some(1.);
+        game.serve_timer = 0.;
+        for _ in 0..30 {
+            game.update(1. / 120., &InputState::default());
+        }
+        assert!((game.time_left().unwrap() - 0.75).abs() < EPSILON);
+
+        // Waiting for the serve doesn't use up any of it
+        game.serve_timer = 0.5;
+        for _ in 0..30 {
+            game.update(1. / 120., &InputState::default());
+        }
+        assert!((game.time_left().unwrap() - 0.75).abs() < EPSILON);
+
+        game.score = Score { left: 3, right: 2 };
+        assert_eq!(game.winner(), None);
+        game.elapsed = 1.;
+        assert_eq!(game.winner(), Some(Side::Left));
+
+        // The next game of the series gets the whole time again
+        game.series = Match::best_of(3);
+        game.finish_game();
+        assert_eq!(game.time_left(), Some(1.));
+    }
+
+    #[test]
+    fn level_at_the_time_limit_goes_to_sudden_death() {
+        let mut game = scripted_game(1);
+        game.time_limit = Some(1.);
+        game.elapsed = 1.;
+        game.score = Score { left: 2, right: 2 };
+        assert_eq!(game.winner(), None);
+        assert_eq!(game.overtime(), Some(Overtime::SuddenDeath));
+
+        game.score.right = 3;
+        assert_eq!(game.winner(), Some(Side::Right));
+        assert_eq!(game.overtime(), None);
+    }
+
+    #[test]
+    fn clear_lead_wins_at_the_target() {
+        let mut game = scripted_game(1);
+        game.score = Score { left: 4, right: 11 };
+        assert_eq!(game.winner(), Some(Side::Right));
+        assert_eq!(game.overtime(), None);
+    }
+
+    #[test]
+    fn collision_boxes_cover_the_paddles_in_play_and_every_ball() {
+        let mut game = scripted_game(2);
+        let boxes = game.collision_boxes();
+        assert_eq!(boxes.len(), 2 + game.balls.len());
+        let (min, max) = boxes[0];
+        let center = game.player_1.center();
+        assert!(min.x < center.x && center.x < max.x && min.y < center.y && center.y < max.y);
+
+        game.practice_wall = true;
+        assert_eq!(game.collision_boxes().len(), 1 + game.balls.len());
+    }
+
+    #[test]
+    fn practice_wall_sends_the_ball_back() {
+        let mut ball = ball_at([0.99, 0.], 1., Wrap64::wrap(0.3));
+
+        assert!(!bounce_off_walls(&mut ball, None));
+        assert!(bounce_off_walls(&mut ball, Some(1.)));
+        assert_direction(
+            ball.velocity_direction,
+            Wrap64::HALF_TURN - Wrap64::wrap(0.3),
+        );
+        let (_, max) = bounds(&ball.vertices);
+        assert!(max.x <= 1.);
+    }
+}
@@ -0,0 +1,83 @@
+use std::{fmt::Write, path::Path};
+
+/// Frames kept at most, about half an hour at 60 FPS. Later frames aren't recorded.
+const MAX_FRAMES: usize = 100_000;
+
+/// Frame timings collected with `--profile`, written out as CSV when the game closes.
+pub struct FrameProfile {
+    /// `(frame, render)` durations in seconds: the whole frame, and building and submitting its
+    /// draw calls.
+    frames: Vec<(f64, f64)>,
+}
+
+impl FrameProfile {
+    pub fn new() -> Self {
+        FrameProfile {
+            frames: Vec::with_capacity(MAX_FRAMES),
+        }
+    }
+
+    pub fn record(&mut self, frame: f64, render: f64) {
+        if self.frames.len() < MAX_FRAMES {
+            self.frames.push((frame, render));
+        }
+    }
+
+    /// Writes one `frame_ms,render_ms` row per frame to `path`.
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let mut csv = String::from("frame_ms,render_ms\n");
+        for (frame, render) in &self.frames {
+            let _ = writeln!(csv, "{:.4},{:.4}", frame * 1_000., render * 1_000.);
+        }
+        std::fs::write(path, csv)
+    }
+
+    /// The p50, p95 and p99 frame and render times, in a line for each.
+    pub fn summary(&self) -> String {
+        let (frames, renders): (Vec<_>, Vec<_>) = self.frames.iter().copied().unzip();
+        let line = |name: &str, mut times: Vec<f64>| {
+            times.sort_by(f64::total_cmp);
+            format!(
+                "{name}: p50 {:.2} ms, p95 {:.2} ms, p99 {:.2} ms",
+                percentile(&times, 50.) * 1_000.,
+                percentile(&times, 95.) * 1_000.,
+                percentile(&times, 99.) * 1_000.
+            )
+        };
+        format!(
+            "{} frames\n{}\n{}",
+            self.frames.len(),
+            line("Frame time", frames),
+            line("Render time", renders)
+        )
+    }
+}
+
+/// The nearest-rank `percent` percentile of `sorted`, 0 when it's empty.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn percentile(sorted: &[f64], percent: f64) -> f64 {
+    let rank = (percent / 100. * sorted.len() as f64).ceil() as usize;
+    sorted
+        .get(rank.saturating_sub(1))
+        .copied()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        let sorted: Vec<f64> = (1..=100).map(f64::from).collect();
+        assert_eq!(percentile(&sorted, 50.).to_bits(), 50_f64.to_bits());
+        assert_eq!(percentile(&sorted, 95.).to_bits(), 95_f64.to_bits());
+        assert_eq!(percentile(&sorted, 99.).to_bits(), 99_f64.to_bits());
+        assert_eq!(percentile(&[7.], 99.).to_bits(), 7_f64.to_bits());
+        assert_eq!(percentile(&[], 50.).to_bits(), 0_f64.to_bits());
+    }
+}
@@ -0,0 +1,33 @@
+use std::path::Path;
+
+use pong::physics::InputState;
+use serde::{Deserialize, Serialize};
+
+/// A recorded match: the seed it was started with and the input held on every physics step.
+/// Played back with the same command-line options, it reproduces the match exactly.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub inputs: Vec<InputState>,
+}
+
+impl Replay {
+    pub fn new(seed: u64) -> Self {
+        Replay {
+            seed,
+            inputs: Vec::new(),
+        }
+    }
+
+    /// Writes the replay to `path` as JSON.
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_string(self).map_err(std::io::Error::other)?;
+        std::fs::write(path, json)
+    }
+
+    /// Reads back a replay written by [`Replay::save`].
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let json = std::fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(std::io::Error::other)
+    }
+}
@@ -0,0 +1,105 @@
+use std::{
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// A frame copied out of the surface texture, waiting to be read back and saved.
+pub struct Screenshot {
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    /// Bytes per row in `buffer`, rounded up to what wgpu requires for texture copies.
+    padded_bytes_per_row: u32,
+    format: wgpu::TextureFormat,
+}
+
+impl Screenshot {
+    /// Records a copy of `texture` into a readable buffer. The texture needs `COPY_SRC` usage.
+    pub fn capture(
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+    ) -> Self {
+        let (width, height) = (texture.width(), texture.height());
+        let padded_bytes_per_row = (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Screenshot Buffer"),
+            size: u64::from(padded_bytes_per_row) * u64::from(height),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+
+        Screenshot {
+            buffer,
+            width,
+            height,
+            padded_bytes_per_row,
+            format: texture.format(),
+        }
+    }
+
+    /// Waits for the copy to finish and writes it as a timestamped PNG in the current directory.
+    /// Must be called after the encoder given to [`Screenshot::capture`] was submitted.
+    pub fn save(self, device: &wgpu::Device) -> Result<PathBuf, String> {
+        let swap_red_blue = match self.format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            format => return Err(format!("Can't save screenshots of {format:?} surfaces")),
+        };
+
+        let slice = self.buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .map_err(|error| error.to_string())?
+            .map_err(|error| error.to_string())?;
+
+        // Drop the row padding, and turn BGRA into the RGBA order PNGs use
+        let row_length = self.width as usize * 4;
+        let mut pixels = Vec::with_capacity(row_length * self.height as usize);
+        for row in slice
+            .get_mapped_range()
+            .chunks(self.padded_bytes_per_row as usize)
+        {
+            pixels.extend_from_slice(&row[..row_length]);
+        }
+        self.buffer.unmap();
+        if swap_red_blue {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis());
+        let path = PathBuf::from(format!("screenshot-{timestamp}.png"));
+        image::save_buffer(
+            &path,
+            &pixels,
+            self.width,
+            self.height,
+            image::ExtendedColorType::Rgba8,
+        )
+        .map_err(|error| error.to_string())?;
+
+        Ok(path)
+    }
+}
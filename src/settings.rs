@@ -0,0 +1,96 @@
+use std::path::Path;
+
+use pong::physics::GAME_TARGET_SCORE;
+use serde::{Deserialize, Serialize};
+
+/// Where settings are read from when `--config` isn't given.
+pub const DEFAULT_SETTINGS_PATH: &str = "pong.toml";
+
+/// Preferences kept between runs in a TOML file. Every key is optional and falls back to its
+/// default, and command-line flags override whatever the file says.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// `p1_color = [1.0, 1.0, 1.0]`, red, green and blue in `0.0..=1.0`.
+    pub p1_color: [f32; 3],
+    /// `p2_color = [1.0, 1.0, 1.0]`, red, green and blue in `0.0..=1.0`.
+    pub p2_color: [f32; 3],
+    /// `ball_color = [1.0, 1.0, 1.0]`, red, green and blue in `0.0..=1.0`.
+    pub ball_color: [f32; 3],
+    /// `target_score = 11`, points needed to win.
+    pub target_score: u32,
+    /// `difficulty = "medium"`, one of `"easy"`, `"medium"` or `"hard"`.
+    pub difficulty: String,
+    /// `present_mode = "fifo"`, one of `"fifo"`, `"mailbox"` or `"immediate"`. Left out, the
+    /// surface's preferred mode is used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub present_mode: Option<String>,
+    /// The `[keys]` table.
+    pub keys: KeySettings,
+}
+
+/// Paddle keys by the name of their `glfw::Key` variant, like `"W"`, `"Up"` or `"LeftShift"`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeySettings {
+    /// `p1_up = "W"`
+    pub p1_up: String,
+    /// `p1_down = "S"`
+    pub p1_down: String,
+    /// `p2_up = "Up"`
+    pub p2_up: String,
+    /// `p2_down = "Down"`
+    pub p2_down: String,
+}
+
+impl Default for KeySettings {
+    fn default() -> Self {
+        KeySettings {
+            p1_up: "W".to_string(),
+            p1_down: "S".to_string(),
+            p2_up: "Up".to_string(),
+            p2_down: "Down".to_string(),
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            p1_color: [1., 1., 1.],
+            p2_color: [1., 1., 1.],
+            ball_color: [1., 1., 1.],
+            target_score: GAME_TARGET_SCORE,
+            difficulty: "medium".to_string(),
+            present_mode: None,
+            keys: KeySettings::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Reads the settings at `path`, falling back to the defaults if the file is missing and
+    /// writing them there as well when `create` is set.
+    pub fn load(path: &Path, create: bool) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text)
+                .map_err(|error| format!("Invalid settings in {}: {error}", path.display())),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                let settings = Settings::default();
+                if !create {
+                    return Ok(settings);
+                }
+                let text = toml::to_string(&settings).map_err(|error| error.to_string())?;
+                // Playing with the defaults is fine even if they can't be saved
+                if let Err(error) = std::fs::write(path, text) {
+                    log::warn!(
+                        "Failed to write default settings to {}: {error}",
+                        path.display()
+                    );
+                }
+                Ok(settings)
+            }
+            Err(error) => Err(format!("Failed to read {}: {error}", path.display())),
+        }
+    }
+}
@@ -1,24 +1,97 @@
 struct VertexInput {
     @location(0) position:vec3<f32>,
     @location(1) color: vec3<f32>,
+    @location(2) uv: vec2<f32>,
 };
 
+struct ScreenUniform {
+    resolution: vec2<f32>,
+    // Added to every position, to shake the view
+    offset: vec2<f32>,
+    // Half-extents of the area that has to fit in the window
+    view_half_size: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> screen: ScreenUniform;
+
+struct ModelUniform {
+    // Places the object being drawn, so moving it doesn't mean rewriting its vertices
+    transform: mat4x4<f32>,
+    // Corner radius as a fraction of the object's shorter half-extent, 1 for a circle
+    roundness: f32,
+};
+
+@group(1) @binding(0)
+var<uniform> model: ModelUniform;
+
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+};
+
+// Per-copy data for drawables drawn instanced
+struct InstanceInput {
+    // Added to every vertex position of this copy
+    @location(3) offset: vec2<f32>,
 };
 
 @vertex
 fn vs_main(
     in: VertexInput
 ) -> VertexOutput {
+    return place(in);
+}
+
+@vertex
+fn vs_instanced(
+    in: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    var moved = in;
+    moved.position = in.position + vec3<f32>(instance.offset, 0.0);
+    return place(moved);
+}
+
+// Puts a vertex on screen, through the model matrix and the letterboxed view
+fn place(in: VertexInput) -> VertexOutput {
     var out: VertexOutput;
-    out.clip_position = vec4<f32>(in.position, 1.0);
+    let aspect = screen.resolution.x / screen.resolution.y;
+    // Shrink the view until it fits, leaving bars on the sides or above and below
+    let scale = min(aspect / screen.view_half_size.x, 1.0 / screen.view_half_size.y);
+    let world = model.transform * vec4<f32>(in.position, 1.0);
+    let position = (world.xy + screen.offset) * scale;
+    out.clip_position = vec4<f32>(position.x / aspect, position.y, world.z, 1.0);
     out.color = in.color;
+    out.uv = in.uv;
     return out;
 }
 
+// Signed distance from `p` to a box of half-extents `half_size` with corners rounded by `radius`
+fn rounded_rect_sdf(p: vec2<f32>, half_size: vec2<f32>, radius: f32) -> f32 {
+    let q = abs(p) - half_size + vec2<f32>(radius);
+    return length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Objects are axis-aligned quads with uv running -1 to 1, so the uv change per pixel gives
+    // the object's half-extents in pixels
+    let half_size = 1.0 / fwidth(in.uv);
+    let p = in.uv * half_size;
+    let radius = min(half_size.x, half_size.y) * model.roundness;
+    let distance = rounded_rect_sdf(p, half_size, radius);
+    if distance > 0.5 {
+        discard;
+    }
+
+    let coverage = 1.0 - smoothstep(-0.5, 0.5, distance);
+    return vec4<f32>(in.color, coverage);
+}
+
+// Solid colour for the wireframe outlines, whose uv says nothing about where the edge is
+@fragment
+fn fs_flat(in: VertexOutput) -> @location(0) vec4<f32> {
     return vec4<f32>(in.color, 1.0);
 }
@@ -0,0 +1,93 @@
+/// A named set of colors for the paddles, ball and background, picked with `--theme`.
+pub struct Theme {
+    pub name: &'static str,
+    pub p1_color: [f32; 3],
+    pub p2_color: [f32; 3],
+    pub ball_color: [f32; 3],
+    pub background: [f32; 3],
+}
+
+/// Every theme `--theme` accepts. Add new ones here.
+pub const THEMES: [Theme; 2] = [
+    // The original look, white on black, with the ball warmed just enough to stand out from the
+    // paddles
+    Theme {
+        name: "classic",
+        p1_color: [1., 1., 1.],
+        p2_color: [1., 1., 1.],
+        ball_color: [1., 0.95, 0.6],
+        background: [0., 0., 0.],
+    },
+    // Sky blue, vermillion and yellow from the Okabe-Ito palette, which stay apart under the
+    // common kinds of colour blindness
+    Theme {
+        name: "high-contrast",
+        p1_color: [0.34, 0.71, 0.91],
+        p2_color: [0.84, 0.37, 0.],
+        ball_color: [0.94, 0.89, 0.26],
+        background: [0., 0., 0.],
+    },
+];
+
+/// Looks up a theme by its name, listing the valid ones if there's no such theme.
+pub fn parse_theme(name: &str) -> Result<&'static Theme, String> {
+    THEMES
+        .iter()
+        .find(|theme| theme.name == name)
+        .ok_or_else(|| {
+            let names: Vec<_> = THEMES.iter().map(|theme| theme.name).collect();
+            format!(
+                "Unknown theme `{name}`, expected one of {}",
+                names.join(", ")
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Largest difference in any one channel, enough to tell two flat colours apart.
+    fn contrast(a: [f32; 3], b: [f32; 3]) -> f32 {
+        a.iter()
+            .zip(&b)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0., f32::max)
+    }
+
+    #[test]
+    fn every_theme_shows_the_ball_against_the_background() {
+        for theme in &THEMES {
+            assert!(
+                contrast(theme.ball_color, theme.background) > 0.5,
+                "{}",
+                theme.name
+            );
+        }
+    }
+
+    #[test]
+    fn paddles_never_share_the_ball_color() {
+        for theme in &THEMES {
+            assert!(
+                contrast(theme.ball_color, theme.p1_color) > 0.3,
+                "{}",
+                theme.name
+            );
+            assert!(
+                contrast(theme.ball_color, theme.p2_color) > 0.3,
+                "{}",
+                theme.name
+            );
+        }
+    }
+
+    #[test]
+    fn theme_names_are_unique() {
+        for (index, theme) in THEMES.iter().enumerate() {
+            assert!(THEMES[index + 1..]
+                .iter()
+                .all(|other| other.name != theme.name));
+        }
+    }
+}